@@ -1,14 +1,32 @@
 pub use sea_orm_migration::prelude::*;
 
 mod m20220926_194618_create_table_yakudo_scores;
+mod m20261018_120000_add_verdict_to_yakudo_scores;
+mod m20261018_130000_add_normalization_to_yakudo_scores;
+mod m20261018_140000_add_hashtag_to_yakudo_scores;
+mod m20261018_150000_create_table_job_runs;
+mod m20261018_160000_add_unique_note_id_to_yakudo_scores;
+mod m20261018_170000_create_table_yakudo_images;
+mod m20261018_180000_add_aggregate_to_yakudo_scores;
+mod m20261018_190000_add_user_and_created_at_to_yakudo_scores;
+mod m20261018_200000_create_table_users;
 
 pub struct Migrator;
 
 #[async_trait::async_trait]
 impl MigratorTrait for Migrator {
     fn migrations() -> Vec<Box<dyn MigrationTrait>> {
-        vec![Box::new(
-            m20220926_194618_create_table_yakudo_scores::Migration,
-        )]
+        vec![
+            Box::new(m20220926_194618_create_table_yakudo_scores::Migration),
+            Box::new(m20261018_120000_add_verdict_to_yakudo_scores::Migration),
+            Box::new(m20261018_130000_add_normalization_to_yakudo_scores::Migration),
+            Box::new(m20261018_140000_add_hashtag_to_yakudo_scores::Migration),
+            Box::new(m20261018_150000_create_table_job_runs::Migration),
+            Box::new(m20261018_160000_add_unique_note_id_to_yakudo_scores::Migration),
+            Box::new(m20261018_170000_create_table_yakudo_images::Migration),
+            Box::new(m20261018_180000_add_aggregate_to_yakudo_scores::Migration),
+            Box::new(m20261018_190000_add_user_and_created_at_to_yakudo_scores::Migration),
+            Box::new(m20261018_200000_create_table_users::Migration),
+        ]
     }
 }
@@ -22,7 +22,13 @@ impl MigrationTrait for Migration {
                     .col(ColumnDef::new(YakudoScores::NoteId).string().not_null())
                     .col(ColumnDef::new(YakudoScores::QuoteId).string().not_null())
                     .col(ColumnDef::new(YakudoScores::Score).double().not_null())
-                    .col(ColumnDef::new(YakudoScores::Date).timestamp().not_null())
+                    // with time zone, as PostgreSQL can't decode a plain timestamp into a
+                    // `DateTime<Tz>`. it is a plain `timestamp` on MySQL all the same
+                    .col(
+                        ColumnDef::new(YakudoScores::Date)
+                            .timestamp_with_time_zone()
+                            .not_null(),
+                    )
                     .to_owned(),
             )
             .await
@@ -0,0 +1,60 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(YakudoScores::Table)
+                    .add_column(
+                        ColumnDef::new(YakudoScores::NormalizeLongEdge)
+                            .integer()
+                            .null(),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(YakudoScores::Table)
+                    .add_column(
+                        ColumnDef::new(YakudoScores::NormalizeDenoiseSigma)
+                            .double()
+                            .null(),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(YakudoScores::Table)
+                    .drop_column(YakudoScores::NormalizeDenoiseSigma)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(YakudoScores::Table)
+                    .drop_column(YakudoScores::NormalizeLongEdge)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(Iden)]
+enum YakudoScores {
+    Table,
+    NormalizeLongEdge,
+    NormalizeDenoiseSigma,
+}
@@ -0,0 +1,45 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(YakudoScores::Table)
+                    .add_column(ColumnDef::new(YakudoScores::Hashtag).string().null())
+                    .to_owned(),
+            )
+            .await?;
+
+        // only #mis1yakudo was monitored before multiple hashtags were supported
+        manager
+            .exec_stmt(
+                Query::update()
+                    .table(YakudoScores::Table)
+                    .value(YakudoScores::Hashtag, "mis1yakudo")
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(YakudoScores::Table)
+                    .drop_column(YakudoScores::Hashtag)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(Iden)]
+enum YakudoScores {
+    Table,
+    Hashtag,
+}
@@ -0,0 +1,61 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // keep only the first record of notes that were processed more than once. the subquery is
+        // wrapped in a derived table because MySQL can't select from the table being deleted from
+        let first_ids = Query::select()
+            .expr_as(Expr::col(YakudoScores::Id).min(), YakudoScores::Id)
+            .from(YakudoScores::Table)
+            .group_by_col(YakudoScores::NoteId)
+            .to_owned();
+        manager
+            .exec_stmt(
+                Query::delete()
+                    .from_table(YakudoScores::Table)
+                    .and_where(
+                        Expr::col(YakudoScores::Id).not_in_subquery(
+                            Query::select()
+                                .column(YakudoScores::Id)
+                                .from_subquery(first_ids, Alias::new("first_ids"))
+                                .to_owned(),
+                        ),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_yakudo_scores_note_id")
+                    .table(YakudoScores::Table)
+                    .col(YakudoScores::NoteId)
+                    .unique()
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("idx_yakudo_scores_note_id")
+                    .table(YakudoScores::Table)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(Iden)]
+enum YakudoScores {
+    Table,
+    Id,
+    NoteId,
+}
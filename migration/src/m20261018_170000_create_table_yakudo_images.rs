@@ -0,0 +1,74 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(YakudoImages::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(YakudoImages::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(YakudoImages::YakudoScoreId)
+                            .integer()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(YakudoImages::FileId).string().not_null())
+                    .col(ColumnDef::new(YakudoImages::Url).text().not_null())
+                    .col(ColumnDef::new(YakudoImages::Mime).string().not_null())
+                    .col(ColumnDef::new(YakudoImages::Width).integer().null())
+                    .col(ColumnDef::new(YakudoImages::Height).integer().null())
+                    .col(ColumnDef::new(YakudoImages::Score).double().not_null())
+                    .col(
+                        ColumnDef::new(YakudoImages::ScorerVersion)
+                            .string()
+                            .not_null(),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_yakudo_images_yakudo_score_id")
+                            .from(YakudoImages::Table, YakudoImages::YakudoScoreId)
+                            .to(YakudoScores::Table, YakudoScores::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(YakudoImages::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(Iden)]
+enum YakudoImages {
+    Table,
+    Id,
+    YakudoScoreId,
+    FileId,
+    Url,
+    Mime,
+    Width,
+    Height,
+    Score,
+    ScorerVersion,
+}
+
+#[derive(Iden)]
+enum YakudoScores {
+    Table,
+    Id,
+}
@@ -0,0 +1,111 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+const INDEXES: &[(&str, YakudoScores)] = &[
+    ("idx_yakudo_scores_date", YakudoScores::Date),
+    ("idx_yakudo_scores_user_id", YakudoScores::UserId),
+    ("idx_yakudo_scores_score", YakudoScores::Score),
+];
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // one column per statement, as SQLite can't add several at once
+        for column in [
+            ColumnDef::new(YakudoScores::UserId)
+                .string()
+                .null()
+                .to_owned(),
+            ColumnDef::new(YakudoScores::UserHost)
+                .string()
+                .null()
+                .to_owned(),
+            ColumnDef::new(YakudoScores::ScorerVersion)
+                .string()
+                .null()
+                .to_owned(),
+            ColumnDef::new(YakudoScores::CreatedAt)
+                .timestamp_with_time_zone()
+                .null()
+                .to_owned(),
+        ] {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(YakudoScores::Table)
+                        .add_column(column)
+                        .to_owned(),
+                )
+                .await?;
+        }
+
+        // the time the existing records were made is known. their user ids and hosts are filled
+        // in from their notes by the bot at startup, and their scorers are unknown
+        manager
+            .exec_stmt(
+                Query::update()
+                    .table(YakudoScores::Table)
+                    .value_expr(YakudoScores::CreatedAt, Expr::col(YakudoScores::Date))
+                    .to_owned(),
+            )
+            .await?;
+
+        for (name, column) in INDEXES {
+            manager
+                .create_index(
+                    Index::create()
+                        .name(*name)
+                        .table(YakudoScores::Table)
+                        .col(*column)
+                        .to_owned(),
+                )
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        for (name, _) in INDEXES {
+            manager
+                .drop_index(
+                    Index::drop()
+                        .name(*name)
+                        .table(YakudoScores::Table)
+                        .to_owned(),
+                )
+                .await?;
+        }
+
+        for column in [
+            YakudoScores::UserId,
+            YakudoScores::UserHost,
+            YakudoScores::ScorerVersion,
+            YakudoScores::CreatedAt,
+        ] {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(YakudoScores::Table)
+                        .drop_column(column)
+                        .to_owned(),
+                )
+                .await?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, Iden)]
+enum YakudoScores {
+    Table,
+    Date,
+    Score,
+    UserId,
+    UserHost,
+    ScorerVersion,
+    CreatedAt,
+}
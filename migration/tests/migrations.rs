@@ -0,0 +1,109 @@
+//! Runs every migration up and down on each backend enabled by a feature. SQLite runs in memory.
+//! PostgreSQL and MySQL need an empty database at `MIGRATION_TEST_POSTGRES_URL` and
+//! `MIGRATION_TEST_MYSQL_URL`, and are skipped without one.
+
+use migration::{Migrator, MigratorTrait};
+use sea_orm_migration::sea_orm::{
+    ConnectOptions, ConnectionTrait, Database, DatabaseConnection, Statement,
+};
+
+/// The number of migrations before `m20261018_160000_add_unique_note_id_to_yakudo_scores`.
+const BEFORE_UNIQUE_NOTE_ID: u32 = 5;
+
+async fn execute(db: &DatabaseConnection, sql: &str) {
+    db.execute(Statement::from_string(
+        db.get_database_backend(),
+        sql.to_string(),
+    ))
+    .await
+    .unwrap_or_else(|err| panic!("{}: {}", sql, err));
+}
+
+async fn count(db: &DatabaseConnection, sql: &str) -> i64 {
+    db.query_one(Statement::from_string(
+        db.get_database_backend(),
+        sql.to_string(),
+    ))
+    .await
+    .unwrap()
+    .unwrap()
+    .try_get("", "n")
+    .unwrap()
+}
+
+async fn up_and_down(url: &str) {
+    let mut options = ConnectOptions::new(url.to_string());
+    // each connection to an in-memory SQLite database is a database of its own
+    options.max_connections(1);
+    let db = Database::connect(options).await.unwrap();
+
+    // records made before the later migrations, including a note recorded twice
+    Migrator::up(&db, Some(BEFORE_UNIQUE_NOTE_ID))
+        .await
+        .unwrap();
+    for (note_id, quote_id) in [("n1", "q1"), ("n1", "q2"), ("n2", "q3")] {
+        execute(
+            &db,
+            &format!(
+                "INSERT INTO yakudo_scores (username, note_id, quote_id, score, date, hashtag) \
+                 VALUES ('alice', '{}', '{}', 1.5, '2022-10-01 12:00:00', 'mis1yakudo')",
+                note_id, quote_id
+            ),
+        )
+        .await;
+    }
+
+    Migrator::up(&db, None).await.unwrap();
+    assert_eq!(
+        count(&db, "SELECT COUNT(*) AS n FROM yakudo_scores").await,
+        2,
+        "the duplicate record is removed"
+    );
+    assert_eq!(
+        count(
+            &db,
+            "SELECT COUNT(*) AS n FROM yakudo_scores WHERE quote_id = 'q1'"
+        )
+        .await,
+        1,
+        "the first record of the note is kept"
+    );
+    assert_eq!(
+        count(
+            &db,
+            "SELECT COUNT(*) AS n FROM yakudo_scores WHERE created_at IS NULL"
+        )
+        .await,
+        0,
+        "created_at is backfilled"
+    );
+
+    Migrator::down(&db, None).await.unwrap();
+    // down must leave nothing behind that breaks the next up
+    Migrator::up(&db, None).await.unwrap();
+    Migrator::down(&db, None).await.unwrap();
+}
+
+#[cfg(feature = "sqlite")]
+#[async_std::test]
+async fn sqlite() {
+    up_and_down("sqlite::memory:").await;
+}
+
+#[cfg(feature = "postgres")]
+#[async_std::test]
+async fn postgres() {
+    match std::env::var("MIGRATION_TEST_POSTGRES_URL") {
+        Ok(url) => up_and_down(&url).await,
+        Err(_) => eprintln!("skipped: MIGRATION_TEST_POSTGRES_URL is not set"),
+    }
+}
+
+#[cfg(feature = "mysql")]
+#[async_std::test]
+async fn mysql() {
+    match std::env::var("MIGRATION_TEST_MYSQL_URL") {
+        Ok(url) => up_and_down(&url).await,
+        Err(_) => eprintln!("skipped: MIGRATION_TEST_MYSQL_URL is not set"),
+    }
+}
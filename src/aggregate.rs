@@ -0,0 +1,200 @@
+use std::{fmt::Display, str::FromStr};
+
+use serde::Deserialize;
+
+/// The proportion trimmed from each end by `trimmed_mean` without a parameter.
+const DEFAULT_TRIM: f64 = 0.2;
+/// The number of scores averaged by `best_of` without a parameter.
+const DEFAULT_BEST_OF: usize = 2;
+
+/// How several scores (e.g. the frames of an animation or the images of a note) are combined
+/// into one. Written as `max`, `mean`, `median`, `trimmed_mean[:<proportion>]` or
+/// `best_of[:<n>]`.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+#[serde(try_from = "String")]
+pub enum Aggregate {
+    Max,
+    Mean,
+    Median,
+    /// The mean after dropping this proportion of the scores from each end.
+    TrimmedMean(f64),
+    /// The mean of the best n scores.
+    BestOf(usize),
+}
+impl Aggregate {
+    /// Combines `scores`. Returns NaN if `scores` is empty.
+    pub fn apply(&self, scores: &[f64]) -> f64 {
+        if scores.is_empty() {
+            return f64::NAN;
+        }
+        let mut sorted = scores.to_vec();
+        sorted.sort_by(f64::total_cmp);
+
+        match self {
+            Aggregate::Max => sorted[sorted.len() - 1],
+            Aggregate::Mean => mean(&sorted),
+            Aggregate::Median => {
+                let middle = sorted.len() / 2;
+                if sorted.len() % 2 == 0 {
+                    (sorted[middle - 1] + sorted[middle]) / 2.0
+                } else {
+                    sorted[middle]
+                }
+            }
+            Aggregate::TrimmedMean(proportion) => {
+                let trim = (sorted.len() as f64 * proportion).floor() as usize;
+                mean(&sorted[trim..sorted.len() - trim])
+            }
+            Aggregate::BestOf(n) => mean(&sorted[sorted.len().saturating_sub(*n)..]),
+        }
+    }
+}
+
+fn mean(scores: &[f64]) -> f64 {
+    scores.iter().sum::<f64>() / scores.len() as f64
+}
+
+impl Display for Aggregate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Aggregate::Max => write!(f, "max"),
+            Aggregate::Mean => write!(f, "mean"),
+            Aggregate::Median => write!(f, "median"),
+            Aggregate::TrimmedMean(proportion) => write!(f, "trimmed_mean:{}", proportion),
+            Aggregate::BestOf(n) => write!(f, "best_of:{}", n),
+        }
+    }
+}
+impl FromStr for Aggregate {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, parameter) = match s.split_once(':') {
+            Some((name, parameter)) => (name, Some(parameter)),
+            None => (s, None),
+        };
+        match (name, parameter) {
+            ("max", None) => Ok(Aggregate::Max),
+            ("mean", None) => Ok(Aggregate::Mean),
+            ("median", None) => Ok(Aggregate::Median),
+            ("trimmed_mean", parameter) => {
+                let proportion = match parameter {
+                    Some(parameter) => parameter.parse()?,
+                    None => DEFAULT_TRIM,
+                };
+                if !(0.0..0.5).contains(&proportion) {
+                    return Err(anyhow::anyhow!(
+                        "trimmed_mean proportion must be at least 0 and less than 0.5"
+                    ));
+                }
+                Ok(Aggregate::TrimmedMean(proportion))
+            }
+            ("best_of", parameter) => {
+                let n = match parameter {
+                    Some(parameter) => parameter.parse()?,
+                    None => DEFAULT_BEST_OF,
+                };
+                if n == 0 {
+                    return Err(anyhow::anyhow!("best_of n must be positive"));
+                }
+                Ok(Aggregate::BestOf(n))
+            }
+            _ => Err(anyhow::anyhow!(
+                "unknown aggregate: {} (available: max, mean, median, trimmed_mean[:<proportion>], \
+                 best_of[:<n>])",
+                s
+            )),
+        }
+    }
+}
+impl TryFrom<String> for Aggregate {
+    type Error = anyhow::Error;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn apply() {
+        let scores = [4.0, 1.0, 3.0, 2.0];
+        assert_eq!(Aggregate::Max.apply(&scores), 4.0);
+        assert_eq!(Aggregate::Mean.apply(&scores), 2.5);
+        // the mean of the middle two for an even count
+        assert_eq!(Aggregate::Median.apply(&scores), 2.5);
+        assert_eq!(Aggregate::Median.apply(&[3.0, 1.0, 2.0]), 2.0);
+        assert!(Aggregate::Mean.apply(&[]).is_nan());
+    }
+
+    #[test]
+    fn trimmed_mean() {
+        let scores = [100.0, 1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0, -100.0];
+        // 10 * 0.2 = 2 scores dropped from each end
+        assert_eq!(Aggregate::TrimmedMean(0.2).apply(&scores), 4.5);
+        // 3 * 0.2 rounds down to nothing dropped
+        assert_eq!(Aggregate::TrimmedMean(0.2).apply(&[1.0, 2.0, 6.0]), 3.0);
+        assert_eq!(Aggregate::TrimmedMean(0.0).apply(&[1.0, 2.0, 6.0]), 3.0);
+    }
+
+    #[test]
+    fn best_of() {
+        assert_eq!(Aggregate::BestOf(2).apply(&[1.0, 5.0, 3.0]), 4.0);
+        // all of them when there are fewer than n
+        assert_eq!(Aggregate::BestOf(5).apply(&[1.0, 5.0, 3.0]), 3.0);
+    }
+
+    #[test]
+    fn parse() {
+        assert_eq!("max".parse::<Aggregate>().unwrap(), Aggregate::Max);
+        assert_eq!("median".parse::<Aggregate>().unwrap(), Aggregate::Median);
+        assert_eq!(
+            "trimmed_mean".parse::<Aggregate>().unwrap(),
+            Aggregate::TrimmedMean(DEFAULT_TRIM)
+        );
+        assert_eq!(
+            "trimmed_mean:0.1".parse::<Aggregate>().unwrap(),
+            Aggregate::TrimmedMean(0.1)
+        );
+        assert_eq!(
+            "best_of".parse::<Aggregate>().unwrap(),
+            Aggregate::BestOf(DEFAULT_BEST_OF)
+        );
+        assert_eq!(
+            "best_of:3".parse::<Aggregate>().unwrap(),
+            Aggregate::BestOf(3)
+        );
+
+        for invalid in [
+            "min",
+            "max:1",
+            "mean:2",
+            "trimmed_mean:0.5",
+            "trimmed_mean:-0.1",
+            "trimmed_mean:x",
+            "best_of:0",
+            "best_of:-1",
+        ] {
+            assert!(invalid.parse::<Aggregate>().is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn display_round_trips() {
+        for aggregate in [
+            Aggregate::Max,
+            Aggregate::Mean,
+            Aggregate::Median,
+            Aggregate::TrimmedMean(0.25),
+            Aggregate::BestOf(3),
+        ] {
+            assert_eq!(
+                aggregate.to_string().parse::<Aggregate>().unwrap(),
+                aggregate
+            );
+        }
+    }
+}
@@ -0,0 +1,107 @@
+use std::io::Cursor;
+
+use anyhow::Context;
+use image::{
+    codecs::{gif::GifDecoder, png::PngDecoder, webp::WebPDecoder},
+    AnimationDecoder, Frames, ImageFormat, RgbaImage,
+};
+use opencv::{core::Mat, prelude::*};
+use serde::Deserialize;
+
+/// How frames are sampled from animated GIF, APNG and WebP images.
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AnimationSampling {
+    /// The maximum number of frames, spread evenly over the animation, to score.
+    pub max_frames: usize,
+}
+impl Default for AnimationSampling {
+    fn default() -> Self {
+        Self { max_frames: 10 }
+    }
+}
+
+/// Decodes the sampled frames of an animated image as BGR images. Returns `None` if the image is
+/// not animated, in which case it should be decoded with OpenCV as usual. The format is sniffed
+/// from the bytes because instances often serve APNG as `image/png`.
+///
+/// The animation is decoded twice, once to count the frames and once to convert the sampled ones,
+/// so that only one frame of a long animation is held at a time.
+pub fn decode_frames(
+    bytes: &[u8],
+    sampling: AnimationSampling,
+) -> anyhow::Result<Option<Vec<Mat>>> {
+    let total = match frames(bytes)? {
+        Some(mut frames) => frames
+            .try_fold(0, |total, frame| frame.map(|_| total + 1))
+            .context("failed to decode animation frames")?,
+        None => return Ok(None),
+    };
+    if total <= 1 {
+        return Ok(None);
+    }
+
+    let count = sampling.max_frames.min(total);
+    // the middle frame of each of `count` equal segments
+    let sampled = (0..count)
+        .map(|i| total * (2 * i + 1) / (2 * count))
+        .collect::<Vec<_>>();
+    let mut mats = Vec::with_capacity(count);
+    for (index, frame) in frames(bytes)?
+        .into_iter()
+        .flatten()
+        .enumerate()
+        .take(sampled[count - 1] + 1)
+    {
+        let frame = frame.context("failed to decode animation frames")?;
+        if sampled.binary_search(&index).is_ok() {
+            mats.push(to_mat(frame.buffer())?);
+        }
+    }
+    Ok(Some(mats))
+}
+
+/// Returns the frames of `bytes` if it is an animated image.
+fn frames(bytes: &[u8]) -> anyhow::Result<Option<Frames<'_>>> {
+    let format = match image::guess_format(bytes) {
+        Ok(format) => format,
+        Err(_) => return Ok(None),
+    };
+
+    Ok(Some(match format {
+        ImageFormat::Gif => GifDecoder::new(Cursor::new(bytes))?.into_frames(),
+        ImageFormat::Png => {
+            let decoder = PngDecoder::new(Cursor::new(bytes))?;
+            if !decoder.is_apng() {
+                return Ok(None);
+            }
+            decoder.apng().into_frames()
+        }
+        ImageFormat::WebP => {
+            let decoder = WebPDecoder::new(Cursor::new(bytes))?;
+            if !decoder.has_animation() {
+                return Ok(None);
+            }
+            decoder.into_frames()
+        }
+        _ => return Ok(None),
+    }))
+}
+
+fn to_mat(buffer: &RgbaImage) -> anyhow::Result<Mat> {
+    let (width, height) = buffer.dimensions();
+    let mut mat = Mat::new_rows_cols_with_default(
+        height as i32,
+        width as i32,
+        opencv::core::CV_8UC3,
+        opencv::core::Scalar::all(0.0),
+    )?;
+    for (dst, src) in mat
+        .data_bytes_mut()?
+        .chunks_exact_mut(3)
+        .zip(buffer.pixels())
+    {
+        dst.copy_from_slice(&[src[2], src[1], src[0]]);
+    }
+    Ok(mat)
+}
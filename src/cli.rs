@@ -0,0 +1,184 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use reqwest::Url;
+use serde::Serialize;
+
+use crate::{
+    config,
+    scorer::{self, ImageScore, Pipeline},
+};
+
+const USAGE: &str = "usage: yakudobot_rs score [--json] [--scorer <name>] [--long-edge <px>] \
+                     [--denoise <sigma>] <path|dir|url>...";
+
+const IMAGE_EXTENSIONS: &[&str] = &[
+    "jpg", "jpeg", "png", "apng", "gif", "webp", "bmp", "tif", "tiff",
+];
+
+#[derive(Serialize)]
+struct ScoreResult {
+    input: String,
+    /// For animations, the first frame with the score of all the frames combined, as the bot
+    /// records it.
+    #[serde(flatten)]
+    score: Option<ImageScore>,
+    /// The scores of the sampled frames of an animation.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    frames: Option<Vec<f64>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    aggregate: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+/// Entry point of the `score` subcommand. Scores local files, directories and URLs without
+/// touching Misskey or the database, and fails if any input could not be scored.
+pub async fn score(args: &[String]) -> anyhow::Result<()> {
+    let mut json = false;
+    let mut pipeline = Pipeline::from_config(&config::get().scoring)?;
+    let mut inputs = vec![];
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--json" => json = true,
+            "--scorer" => {
+                pipeline.scorer = scorer::from_name(args.next().context(USAGE)?)?;
+            }
+            "--long-edge" => {
+                pipeline.normalization.long_edge = Some(args.next().context(USAGE)?.parse()?);
+            }
+            "--denoise" => {
+                pipeline.normalization.denoise_sigma = Some(args.next().context(USAGE)?.parse()?);
+            }
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return Ok(());
+            }
+            _ => inputs.push(arg.clone()),
+        }
+    }
+    if inputs.is_empty() {
+        return Err(anyhow::anyhow!(USAGE));
+    }
+
+    let mut results = vec![];
+    for input in inputs {
+        for input in expand_input(&input)? {
+            let result = match score_input(&pipeline, &input).await {
+                Ok(frames) if frames.len() > 1 => {
+                    let scores = frames.iter().map(|f| f.score).collect::<Vec<_>>();
+                    ScoreResult {
+                        input,
+                        score: Some(ImageScore {
+                            score: pipeline.frame_aggregate.apply(&scores),
+                            ..frames[0]
+                        }),
+                        frames: Some(scores),
+                        aggregate: Some(pipeline.frame_aggregate.to_string()),
+                        error: None,
+                    }
+                }
+                Ok(frames) => ScoreResult {
+                    input,
+                    score: Some(frames[0]),
+                    frames: None,
+                    aggregate: None,
+                    error: None,
+                },
+                Err(err) => ScoreResult {
+                    input,
+                    score: None,
+                    frames: None,
+                    aggregate: None,
+                    error: Some(format!("{:#}", err)),
+                },
+            };
+            if !json {
+                match (&result.score, &result.error) {
+                    (Some(score), _) => {
+                        print!(
+                            "{}\t{:.3}\tbase={:.3}\tblur={:.0}deg\tanisotropy={:.3}",
+                            result.input,
+                            score.score,
+                            score.base_score,
+                            score.motion_blur.angle,
+                            score.motion_blur.anisotropy
+                        );
+                        if let (Some(frames), Some(aggregate)) = (&result.frames, &result.aggregate)
+                        {
+                            print!(
+                                "\tframes={} [{}]",
+                                frames
+                                    .iter()
+                                    .map(|s| format!("{:.3}", s))
+                                    .collect::<Vec<_>>()
+                                    .join("/"),
+                                aggregate
+                            );
+                        }
+                        println!();
+                    }
+                    (_, Some(error)) => println!("{}\terror: {}", result.input, error),
+                    _ => unreachable!(),
+                }
+            }
+            results.push(result);
+        }
+    }
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&results)?);
+    }
+
+    let failed = results.iter().filter(|r| r.error.is_some()).count();
+    if failed > 0 {
+        return Err(anyhow::anyhow!("failed to score {} input(s)", failed));
+    }
+
+    Ok(())
+}
+
+/// Expands a directory into the image files directly inside it, sorted by name. URLs and
+/// plain files are returned as is.
+fn expand_input(input: &str) -> anyhow::Result<Vec<String>> {
+    if is_url(input) || !Path::new(input).is_dir() {
+        return Ok(vec![input.to_string()]);
+    }
+
+    let mut paths = std::fs::read_dir(input)
+        .with_context(|| format!("failed to read directory: {}", input))?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<Vec<PathBuf>, _>>()?;
+    paths.retain(|path| {
+        path.is_file()
+            && path
+                .extension()
+                .and_then(|ext| ext.to_str())
+                .map(|ext| IMAGE_EXTENSIONS.contains(&ext.to_ascii_lowercase().as_str()))
+                .unwrap_or(false)
+    });
+    paths.sort();
+
+    Ok(paths
+        .into_iter()
+        .map(|path| path.to_string_lossy().into_owned())
+        .collect())
+}
+
+/// Scores an image like the bot does, returning the scores of the sampled frames of an animation
+/// or the single score of a still image.
+async fn score_input(pipeline: &Pipeline, input: &str) -> anyhow::Result<Vec<ImageScore>> {
+    let image_bytes = if is_url(input) {
+        let url = Url::parse(input)?;
+        reqwest::get(url).await?.bytes().await?.to_vec()
+    } else {
+        std::fs::read(input).with_context(|| format!("failed to read file: {}", input))?
+    };
+    pipeline.score_frames_bytes(&image_bytes)
+}
+
+fn is_url(input: &str) -> bool {
+    input.starts_with("http://") || input.starts_with("https://")
+}
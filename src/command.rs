@@ -0,0 +1,296 @@
+use std::{str::FromStr, sync::Arc};
+
+use chrono::Local;
+use misskey::{
+    model::{id::Id, note::Note},
+    ClientExt,
+};
+
+use anyhow::Context;
+use sea_orm::prelude::*;
+
+use crate::{
+    config,
+    database::get_db,
+    entity::yakudo_score,
+    metrics,
+    misskey::Misskey,
+    monitor::{record_error, rescore_note},
+    report,
+    scorer::Pipeline,
+    stats,
+    users::{self, UserKey},
+};
+
+const HELP: &str = "コマンド一覧\n\
+                    rank: 今日の順位\n\
+                    best: 自己ベストと記録\n\
+                    today: 今日のランキング\n\
+                    rescore: 返信先のノートのスコアを再計算\n\
+                    help: このメッセージ";
+
+/// A command sent to the bot by mentioning it, e.g. `@yakudobot rank`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Command {
+    Rank,
+    Best,
+    Today,
+    /// Scores the note replied to again, replacing the old quote and record. Only for the author
+    /// of the note and the admins.
+    Rescore,
+    Help,
+}
+impl FromStr for Command {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "rank" => Ok(Command::Rank),
+            "best" => Ok(Command::Best),
+            "today" => Ok(Command::Today),
+            "rescore" => Ok(Command::Rescore),
+            "help" => Ok(Command::Help),
+            _ => Err(anyhow::anyhow!("unknown command: {}", s)),
+        }
+    }
+}
+
+/// Returns the first word of `text` that is not a mention, which is the command name.
+fn command_name(text: &str) -> Option<&str> {
+    text.split_whitespace().find(|word| !word.starts_with('@'))
+}
+
+/// Answers a note mentioning the bot. Notes without a known command, such as plain replies to
+/// the bot's quotes, are ignored.
+pub async fn process_mention(misskey: Arc<Misskey>, note: Note) -> anyhow::Result<()> {
+    if note.user.id == misskey.user_id() {
+        return Ok(());
+    }
+    let name = match note.text.as_deref().and_then(command_name) {
+        Some(name) => name,
+        None => return Ok(()),
+    };
+    let command = match name.parse::<Command>() {
+        Ok(command) => command,
+        Err(_) => return Ok(()),
+    };
+    let user = UserKey::from(&note.user);
+    info!("command from @{}: {:?}", user.acct(), command);
+    if let Err(err) = users::remember(&note.user).await {
+        warn!("failed to cache user {}: {:#}", user.acct(), err);
+    }
+
+    let message = match command {
+        Command::Rank => rank(&user).await?,
+        Command::Best => best(&misskey, &user).await?,
+        Command::Today => today(&misskey).await?,
+        Command::Rescore => match rescore(misskey.clone(), &note).await? {
+            Some(message) => message,
+            // the new quote is the answer
+            None => return Ok(()),
+        },
+        Command::Help => HELP.to_string(),
+    };
+
+    metrics::api_result("reply", misskey.reply(&note, &message).await)?;
+    info!("message: {}", message);
+
+    Ok(())
+}
+
+/// The place of the user in today's ranking of each hashtag with a leaderboard.
+async fn rank(user: &UserKey) -> anyhow::Result<String> {
+    let config = config::get();
+    let today = Local::now().date_naive();
+
+    let mut message = String::new();
+    for hashtag in config.hashtags.iter().filter(|h| h.leaderboard) {
+        let yakudos = report::yakudos_between(&hashtag.name, today, today.succ_opt().unwrap())
+            .await?
+            .into_iter()
+            .filter(|y| y.score > 0.0)
+            .collect::<Vec<_>>();
+        let ranking = report::rank(yakudos, usize::MAX);
+        let place = ranking.iter().find(|ranked| user.matches(&ranked.yakudo));
+
+        if config.hashtags.len() > 1 {
+            message.push_str(&format!("#{} ", hashtag.name));
+        }
+        match place {
+            Some(ranked) => message.push_str(&format!(
+                "今日の順位:{}位/{}人 Score:{:.3}\n",
+                ranked.place,
+                ranking.len(),
+                ranked.yakudo.score
+            )),
+            None => message.push_str("今日のyakudoはまだありません\n"),
+        }
+    }
+    Ok(message)
+}
+
+async fn best(misskey: &Misskey, user: &UserKey) -> anyhow::Result<String> {
+    let stats = stats::user_stats(user, None).await?;
+    if stats.posts == 0 {
+        return Ok("まだyakudoしていません".to_string());
+    }
+
+    let mut message = format!("投稿:{}件\n", stats.posts);
+    if let (Some(best), Some(note_id)) = (stats.best, &stats.best_note_id) {
+        message.push_str(&format!(
+            "自己ベスト:{:.3} {}\n",
+            best,
+            misskey.get_note_url(note_id.parse::<Id<Note>>()?)
+        ));
+    }
+    if let Some(average) = stats.average {
+        message.push_str(&format!("平均スコア:{:.3}\n", average));
+    }
+    message.push_str(&format!(
+        "連続投稿:{}日 (最長{}日)\n",
+        stats.streak, stats.longest_streak
+    ));
+    if !stats.rank_history.is_empty() {
+        message.push_str(&format!("最近の判定:{}\n", stats.rank_history.join(" ")));
+    }
+    Ok(message)
+}
+
+/// Today's ranking of each hashtag with a leaderboard so far.
+async fn today(misskey: &Misskey) -> anyhow::Result<String> {
+    let config = config::get();
+    let today = Local::now().date_naive();
+
+    let mut message = String::new();
+    for hashtag in config.hashtags.iter().filter(|h| h.leaderboard) {
+        let yakudos = report::yakudos_between(&hashtag.name, today, today.succ_opt().unwrap())
+            .await?
+            .into_iter()
+            .filter(|y| y.score > 0.0)
+            .collect();
+        let ranking = report::rank(yakudos, config.report.daily_top_n);
+
+        if config.hashtags.len() > 1 {
+            message.push_str(&format!("#{}\n", hashtag.name));
+        }
+        if ranking.is_empty() {
+            message.push_str("今日のyakudoはまだありません\n");
+        } else {
+            // only the asking user should be notified of the reply
+            message.push_str(&report::format_ranking(misskey, &ranking, false)?);
+        }
+    }
+    Ok(message)
+}
+
+/// Scores the note `command` replies to again, replacing its quotes and records. Returns the
+/// reply when the note is not rescored.
+async fn rescore(misskey: Arc<Misskey>, command: &Note) -> anyhow::Result<Option<String>> {
+    let config = config::get();
+
+    let target_id = match command.reply_id {
+        Some(id) => id,
+        None => {
+            return Ok(Some(
+                "スコアを再計算するノートに返信してください".to_string(),
+            ))
+        }
+    };
+    let mut target = metrics::api_result("get_note", misskey.get_note(target_id).await)
+        .context("failed to get the note to rescore")?;
+    // replies are scored as the note they reply to, as in `process_note`
+    while let Some(reply_id) = target.reply_id {
+        target = metrics::api_result("get_note", misskey.get_note(reply_id).await)
+            .context("failed to get the note that this note is replying to")?;
+    }
+    // a reply to the bot's quote of the score rescores the quoted note
+    if target.user.id == misskey.user_id() {
+        if let Some(renote_id) = target.renote_id {
+            target = metrics::api_result("get_note", misskey.get_note(renote_id).await)
+                .context("failed to get the note that this note is quoting")?;
+        }
+    }
+    // as in `process_note`, the bot's own notes and renotes are never scored
+    if target.user.id == misskey.user_id() || target.renote_id.is_some() {
+        return Ok(Some("yakudoのノートではありません".to_string()));
+    }
+
+    let acct = UserKey::from(&command.user).acct();
+    if target.user.id != command.user.id && !config.misskey.admins.contains(&acct) {
+        info!("{} is not allowed to rescore note {}", acct, target.id);
+        return Ok(Some("自分のノートしか再計算できません".to_string()));
+    }
+
+    let old_yakudos = yakudo_score::Entity::find()
+        .filter(yakudo_score::Column::NoteId.eq(target.id.to_string()))
+        .all(get_db().await?)
+        .await
+        .context("failed to get yakudos")?;
+
+    // the hashtag it was scored for, or the first monitored one in the text
+    let hashtag = config
+        .hashtags
+        .iter()
+        .find(|h| {
+            old_yakudos
+                .iter()
+                .any(|y| y.hashtag.as_deref() == Some(h.name.as_str()))
+        })
+        .or_else(|| config.hashtag_of(target.text.as_deref().unwrap_or_default()));
+    let hashtag = match hashtag {
+        Some(hashtag) => hashtag,
+        None => return Ok(Some("yakudoのノートではありません".to_string())),
+    };
+
+    // the old quotes and records are kept if scoring fails
+    let pipeline = Arc::new(Pipeline::from_config(&config.scoring)?);
+    if let Err(err) = rescore_note(misskey, pipeline, hashtag, target, old_yakudos).await {
+        record_error(hashtag, err);
+        return Ok(Some("スコアを計算できませんでした".to_string()));
+    }
+
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(text: &str) -> Option<Command> {
+        command_name(text).and_then(|name| name.parse().ok())
+    }
+
+    #[test]
+    fn mentions_are_skipped() {
+        assert_eq!(parse("@yakudobot rank"), Some(Command::Rank));
+        assert_eq!(
+            parse("@yakudobot@misskey.example rank"),
+            Some(Command::Rank)
+        );
+        assert_eq!(
+            parse("@yakudobot @alice@example.com best"),
+            Some(Command::Best)
+        );
+        assert_eq!(parse("@yakudobot\nhelp"), Some(Command::Help));
+        assert_eq!(parse("@yakudobot"), None);
+    }
+
+    #[test]
+    fn case_is_ignored() {
+        assert_eq!(parse("@yakudobot RANK"), Some(Command::Rank));
+        assert_eq!(parse("@yakudobot ReScore"), Some(Command::Rescore));
+    }
+
+    #[test]
+    fn words_after_the_command_are_ignored() {
+        assert_eq!(parse("@yakudobot today please"), Some(Command::Today));
+        assert_eq!(parse("@yakudobot rescore @alice"), Some(Command::Rescore));
+    }
+
+    #[test]
+    fn unknown_commands() {
+        assert_eq!(parse("@yakudobot ranking"), None);
+        assert_eq!(parse("@yakudobot すごい rank"), None);
+        assert!("".parse::<Command>().is_err());
+    }
+}
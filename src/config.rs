@@ -0,0 +1,465 @@
+use std::{fmt::Display, str::FromStr, sync::OnceLock};
+
+use anyhow::Context;
+use serde::Deserialize;
+
+use crate::{
+    aggregate::Aggregate, animation::AnimationSampling, health::ReconnectPolicy,
+    metrics::HttpConfig, scheduler::CronSchedule, scorer, scorer::Normalization,
+    verdict::VerdictTable, video::VideoSampling,
+};
+
+static CONFIG: OnceLock<Config> = OnceLock::new();
+
+/// Loads the configuration. Must be called once at startup before `get`.
+pub fn init() -> anyhow::Result<&'static Config> {
+    let config = Config::load()?;
+    Ok(CONFIG.get_or_init(|| config))
+}
+
+pub fn get() -> &'static Config {
+    CONFIG.get().expect("config is not initialized")
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub hashtags: Vec<HashtagConfig>,
+    pub misskey: MisskeyConfig,
+    pub database: DatabaseConfig,
+    pub reconnect: ReconnectPolicy,
+    pub http: HttpConfig,
+    pub scoring: ScoringConfig,
+    pub verdicts: VerdictTable,
+    pub schedule: ScheduleConfig,
+    pub report: ReportConfig,
+    pub messages: Messages,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            hashtags: vec![HashtagConfig::new(if cfg!(debug_assertions) {
+                "mis1yakudotest"
+            } else {
+                "mis1yakudo"
+            })],
+            misskey: MisskeyConfig::default(),
+            database: DatabaseConfig::default(),
+            reconnect: ReconnectPolicy::default(),
+            http: HttpConfig::default(),
+            scoring: ScoringConfig::default(),
+            verdicts: VerdictTable::default(),
+            schedule: ScheduleConfig::default(),
+            report: ReportConfig::default(),
+            messages: Messages::default(),
+        }
+    }
+}
+
+/// A monitored hashtag. Settings left out fall back to the top-level ones.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct HashtagConfig {
+    /// Without the leading `#`.
+    pub name: String,
+    pub verdicts: Option<VerdictTable>,
+    pub reply_template: Option<String>,
+    pub image_aggregate: Option<Aggregate>,
+    /// Whether the scheduled reports announce a ranking for this hashtag.
+    #[serde(default = "default_true")]
+    pub leaderboard: bool,
+}
+impl HashtagConfig {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            verdicts: None,
+            reply_template: None,
+            image_aggregate: None,
+            leaderboard: true,
+        }
+    }
+
+    pub fn verdicts<'a>(&'a self, config: &'a Config) -> &'a VerdictTable {
+        self.verdicts.as_ref().unwrap_or(&config.verdicts)
+    }
+
+    pub fn image_aggregate(&self, config: &Config) -> Aggregate {
+        self.image_aggregate
+            .unwrap_or(config.scoring.image_aggregate)
+    }
+
+    pub fn reply_template<'a>(&'a self, config: &'a Config) -> &'a str {
+        self.reply_template
+            .as_deref()
+            .unwrap_or(&config.messages.reply_template)
+    }
+
+    /// Whether `text` contains this hashtag as a whole word, ignoring case like Misskey does.
+    pub fn is_in(&self, text: &str) -> bool {
+        let text = text.to_lowercase();
+        let tag = format!("#{}", self.name.to_lowercase());
+        text.match_indices(&tag).any(|(i, _)| {
+            text[i + tag.len()..]
+                .chars()
+                .next()
+                .is_none_or(|c| !(c.is_alphanumeric() || c == '_'))
+        })
+    }
+}
+
+fn default_true() -> bool {
+    true
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MisskeyConfig {
+    /// The host name of the instance, e.g. `misskey.io`.
+    pub instance: String,
+    pub token: String,
+    /// Whether to connect with https/wss.
+    pub secure: bool,
+    /// Accounts (`user` for local users, `user@host` for remote ones) allowed to use the admin
+    /// commands on any note.
+    pub admins: Vec<String>,
+}
+impl Default for MisskeyConfig {
+    fn default() -> Self {
+        Self {
+            instance: String::new(),
+            token: String::new(),
+            secure: true,
+            admins: vec![],
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DatabaseConfig {
+    /// `mysql://`, `postgres://` or `sqlite://`. The backend must be enabled by its cargo feature.
+    pub url: String,
+}
+impl DatabaseConfig {
+    /// Checks that the backend of `url` is compiled in, so that a wrong build fails at startup
+    /// rather than on every connection attempt.
+    fn check_backend(&self) -> anyhow::Result<()> {
+        let scheme = self.url.split(':').next().unwrap_or_default();
+        let enabled = match scheme {
+            "mysql" => cfg!(feature = "mysql"),
+            "postgres" | "postgresql" => cfg!(feature = "postgres"),
+            "sqlite" => cfg!(feature = "sqlite"),
+            _ => {
+                return Err(anyhow::anyhow!(
+                    "unsupported database.url (DATABASE_URL) scheme: {} (available: mysql, \
+                     postgres, sqlite)",
+                    scheme
+                ))
+            }
+        };
+        if !enabled {
+            return Err(anyhow::anyhow!(
+                "database.url (DATABASE_URL) is {}, but this build lacks the {} feature",
+                scheme,
+                if scheme == "postgresql" {
+                    "postgres"
+                } else {
+                    scheme
+                }
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ScoringConfig {
+    pub scorer: String,
+    /// How the scores of the frames of an animation or a video are combined.
+    pub frame_aggregate: Aggregate,
+    /// How the scores of the attachments of a note are combined.
+    pub image_aggregate: Aggregate,
+    pub normalization: Normalization,
+    pub video: VideoSampling,
+    pub animation: AnimationSampling,
+}
+impl Default for ScoringConfig {
+    fn default() -> Self {
+        Self {
+            scorer: "laplacian".to_string(),
+            frame_aggregate: Aggregate::Mean,
+            image_aggregate: Aggregate::Mean,
+            normalization: Normalization::default(),
+            video: VideoSampling::default(),
+            animation: AnimationSampling::default(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ScheduleConfig {
+    pub daily_report: CronSchedule,
+    pub destroy_deleted_notes: CronSchedule,
+    pub follow_followers: CronSchedule,
+    pub weekly_report: CronSchedule,
+    pub monthly_report: CronSchedule,
+    pub yearly_report: CronSchedule,
+    pub refresh_users: CronSchedule,
+}
+impl Default for ScheduleConfig {
+    fn default() -> Self {
+        let cron = |expression: &str| CronSchedule::try_from(expression.to_string()).unwrap();
+        Self {
+            daily_report: cron("59 23 * * *"),
+            destroy_deleted_notes: cron("50 * * * *"),
+            follow_followers: cron("0 0 * * *"),
+            weekly_report: cron("5 0 * * Mon"),
+            monthly_report: cron("5 0 1 * *"),
+            yearly_report: cron("5 0 1 1 *"),
+            refresh_users: cron("30 4 * * *"),
+        }
+    }
+}
+
+/// Settings of the rankings in the scheduled reports.
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ReportConfig {
+    /// The number of places in the daily ranking. Users tied at the last place are all listed.
+    pub daily_top_n: usize,
+    /// The number of places in the weekly, monthly and yearly rankings.
+    pub top_n: usize,
+    /// Whether the placed users are mentioned.
+    pub mention: bool,
+}
+impl Default for ReportConfig {
+    fn default() -> Self {
+        Self {
+            daily_top_n: 3,
+            top_n: 3,
+            mention: false,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Messages {
+    /// The quote posted for each scored note. `{date}`, `{hashtag}`, `{user}`, `{details}`
+    /// (the per-image scores), `{verdict}`, `{rank}`, `{score}` and `{aggregate}` (how the
+    /// per-image scores were combined) are replaced.
+    pub reply_template: String,
+    pub no_image: String,
+    pub video_rejected: String,
+    /// Appended to the quote when a user beats their best score.
+    pub personal_best: String,
+    pub daily_winner: String,
+    pub daily_no_score: String,
+    pub daily_no_yakudo: String,
+    pub weekly_report_title: String,
+    pub monthly_report_title: String,
+    pub yearly_report_title: String,
+    /// Posted instead of the summary when there was no yakudo in the period.
+    pub periodic_no_yakudo: String,
+}
+impl Default for Messages {
+    fn default() -> Self {
+        Self {
+            reply_template: "{date}\nUser:{user}\n{details}{verdict}\nRank:{rank}\nScore:{score}\n"
+                .to_string(),
+            no_image: "画像が入ってないやん!".to_string(),
+            video_rejected: "やめろ！クソ動画を投稿するんじゃない!".to_string(),
+            personal_best: "自己ベスト更新!\n".to_string(),
+            daily_winner: "優勝おめでとう!".to_string(),
+            daily_no_score: "おい待てや...今日のyakudo...-inf点しか無いやん...".to_string(),
+            daily_no_yakudo: "本日のyakudoは...何一つ...出ませんでした...".to_string(),
+            weekly_report_title: "先週のyakudoランキング".to_string(),
+            monthly_report_title: "先月のyakudoランキング".to_string(),
+            yearly_report_title: "昨年のyakudoランキング".to_string(),
+            periodic_no_yakudo: "yakudoは...何一つ...出ませんでした...".to_string(),
+        }
+    }
+}
+
+impl Config {
+    /// The first monitored hashtag in `text` in the order of the config. A note with several
+    /// monitored hashtags is scored for this one only, whichever stream it arrives on first.
+    pub fn hashtag_of(&self, text: &str) -> Option<&HashtagConfig> {
+        self.hashtags.iter().find(|h| h.is_in(text))
+    }
+
+    /// Reads the TOML file at `CONFIG_PATH` (default: `config.toml`), applies the environment
+    /// variable overrides and validates the result. A missing file is not an error so that the
+    /// bot can still be configured with environment variables only. Neither is a directory in its
+    /// place, which is what Docker mounts when the file is missing on the host.
+    pub fn load() -> anyhow::Result<Self> {
+        let path = std::env::var("CONFIG_PATH").unwrap_or_else(|_| "config.toml".to_string());
+        let mut config = match std::fs::read_to_string(&path) {
+            Err(_) if std::path::Path::new(&path).is_dir() => {
+                warn!("{} is a directory. using default config", path);
+                Config::default()
+            }
+            Ok(content) => {
+                toml::from_str(&content).with_context(|| format!("failed to parse {}", path))?
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                info!("{} not found. using default config", path);
+                Config::default()
+            }
+            Err(e) => return Err(e).with_context(|| format!("failed to read {}", path)),
+        };
+
+        config.apply_env()?;
+        config.validate()?;
+
+        Ok(config)
+    }
+
+    fn apply_env(&mut self) -> anyhow::Result<()> {
+        if let Ok(value) = std::env::var("HASHTAGS") {
+            // keep the settings of hashtags that are also in the file
+            let mut hashtags = std::mem::take(&mut self.hashtags);
+            self.hashtags = value
+                .split(',')
+                .map(str::trim)
+                .filter(|name| !name.is_empty())
+                .map(|name| match hashtags.iter().position(|h| h.name == name) {
+                    Some(i) => hashtags.swap_remove(i),
+                    None => HashtagConfig::new(name),
+                })
+                .collect();
+        }
+        override_from_env(&mut self.misskey.instance, "INSTANCE")?;
+        override_from_env(&mut self.misskey.token, "TOKEN")?;
+        override_from_env(&mut self.misskey.secure, "SECURE")?;
+        override_from_env(&mut self.database.url, "DATABASE_URL")?;
+        override_from_env(&mut self.http.enabled, "HTTP_ENABLED")?;
+        override_from_env(&mut self.http.listen, "HTTP_LISTEN")?;
+
+        let scoring = &mut self.scoring;
+        override_from_env(&mut scoring.scorer, "SCORER")?;
+        override_from_env(&mut scoring.frame_aggregate, "FRAME_AGGREGATE")?;
+        override_from_env(&mut scoring.image_aggregate, "IMAGE_AGGREGATE")?;
+        override_option_from_env(&mut scoring.normalization.long_edge, "NORMALIZE_LONG_EDGE")?;
+        override_option_from_env(
+            &mut scoring.normalization.denoise_sigma,
+            "NORMALIZE_DENOISE_SIGMA",
+        )?;
+        override_from_env(&mut scoring.video.enabled, "VIDEO_SCORING")?;
+        override_from_env(&mut scoring.video.frames, "VIDEO_SAMPLE_FRAMES")?;
+        override_from_env(&mut scoring.animation.max_frames, "ANIMATION_MAX_FRAMES")?;
+
+        Ok(())
+    }
+
+    fn validate(&self) -> anyhow::Result<()> {
+        if self.hashtags.is_empty() {
+            return Err(anyhow::anyhow!("at least one hashtag is required"));
+        }
+        for (i, hashtag) in self.hashtags.iter().enumerate() {
+            if hashtag.name.is_empty() || hashtag.name.starts_with('#') {
+                return Err(anyhow::anyhow!(
+                    "hashtag name must be non-empty and without the leading #"
+                ));
+            }
+            if self.hashtags[..i].iter().any(|h| h.name == hashtag.name) {
+                return Err(anyhow::anyhow!("duplicate hashtag: {}", hashtag.name));
+            }
+        }
+
+        let reconnect = &self.reconnect;
+        // NaN fails no comparison, and infinity would overflow the delay
+        if !reconnect.initial_delay.is_finite()
+            || !reconnect.max_delay.is_finite()
+            || reconnect.initial_delay <= 0.0
+            || reconnect.max_delay < reconnect.initial_delay
+        {
+            return Err(anyhow::anyhow!(
+                "reconnect.initial_delay must be positive and not above reconnect.max_delay, \
+                 and both finite"
+            ));
+        }
+        if reconnect.database_attempts == 0 {
+            return Err(anyhow::anyhow!(
+                "reconnect.database_attempts must be positive"
+            ));
+        }
+
+        scorer::from_name(&self.scoring.scorer)?;
+        let normalization = &self.scoring.normalization;
+        if normalization.long_edge.map(|v| v <= 0).unwrap_or(false) {
+            return Err(anyhow::anyhow!("normalization.long_edge must be positive"));
+        }
+        if normalization
+            .denoise_sigma
+            .map(|v| v <= 0.0)
+            .unwrap_or(false)
+        {
+            return Err(anyhow::anyhow!(
+                "normalization.denoise_sigma must be positive"
+            ));
+        }
+        if self.scoring.video.frames == 0 {
+            return Err(anyhow::anyhow!("video.frames must be positive"));
+        }
+        if self.scoring.animation.max_frames == 0 {
+            return Err(anyhow::anyhow!("animation.max_frames must be positive"));
+        }
+        if self.report.daily_top_n == 0 {
+            return Err(anyhow::anyhow!("report.daily_top_n must be positive"));
+        }
+        if self.report.top_n == 0 {
+            return Err(anyhow::anyhow!("report.top_n must be positive"));
+        }
+
+        Ok(())
+    }
+
+    /// Checks the settings that are only needed to run the bot, so that the `score` subcommand
+    /// works without them.
+    pub fn validate_bot(&self) -> anyhow::Result<()> {
+        if self.misskey.instance.is_empty() {
+            return Err(anyhow::anyhow!("misskey.instance (INSTANCE) is not set"));
+        }
+        if self.misskey.token.is_empty() {
+            return Err(anyhow::anyhow!("misskey.token (TOKEN) is not set"));
+        }
+        if self.database.url.is_empty() {
+            return Err(anyhow::anyhow!("database.url (DATABASE_URL) is not set"));
+        }
+        self.database.check_backend()?;
+        Ok(())
+    }
+}
+
+fn override_from_env<T>(target: &mut T, name: &str) -> anyhow::Result<()>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    if let Ok(value) = std::env::var(name) {
+        *target = value
+            .parse()
+            .map_err(|e| anyhow::anyhow!("{} is invalid: {}", name, e))?;
+    }
+    Ok(())
+}
+
+fn override_option_from_env<T>(target: &mut Option<T>, name: &str) -> anyhow::Result<()>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    if let Ok(value) = std::env::var(name) {
+        *target = Some(
+            value
+                .parse()
+                .map_err(|e| anyhow::anyhow!("{} is invalid: {}", name, e))?,
+        );
+    }
+    Ok(())
+}
@@ -3,17 +3,34 @@ use std::time::Duration;
 use anyhow::Context;
 use async_once_cell::OnceCell;
 use migration::{Migrator, MigratorTrait};
-use sea_orm::{Database, DatabaseConnection};
-use tokio::time::sleep;
+use sea_orm::{ConnectionTrait, Database, DatabaseConnection, Statement};
+use tokio::time::{sleep, timeout};
+
+use crate::{
+    config,
+    health::{self, Backoff},
+};
 
 static DB: OnceCell<DatabaseConnection> = OnceCell::new();
 
+const COMPONENT: &str = "database";
+/// How long `check` waits for the database to answer.
+const CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
 pub async fn get_db() -> anyhow::Result<&'static DatabaseConnection> {
     DB.get_or_try_init(async {
-        for i in 0..10 {
-            info!("connecting to database...(try {}/10)", i + 1);
+        let policy = config::get().reconnect;
+        let mut backoff = Backoff::new(policy);
+        health::register(COMPONENT);
+
+        for i in 0..policy.database_attempts {
+            info!(
+                "connecting to database...(try {}/{})",
+                i + 1,
+                policy.database_attempts
+            );
 
-            match Database::connect(&std::env::var("DATABASE_URL")?).await {
+            match Database::connect(&config::get().database.url).await {
                 Ok(db) => {
                     info!("connected to database");
                     info!("running database migrations...");
@@ -21,12 +38,14 @@ pub async fn get_db() -> anyhow::Result<&'static DatabaseConnection> {
                         .await
                         .context("failed to migrate database")?;
                     info!("database migrations completed");
+                    health::set_up(COMPONENT);
                     return Ok(db);
                 }
                 Err(e) => {
-                    warn!("failed to connect to database: {}", e);
-                    warn!("retrying in 5 seconds...");
-                    sleep(Duration::from_secs(5)).await;
+                    health::set_down(COMPONENT, format!("failed to connect: {}", e), &policy);
+                    let delay = backoff.next_delay();
+                    warn!("retrying in {:.1} seconds...", delay.as_secs_f64());
+                    sleep(delay).await;
                 }
             }
         }
@@ -35,3 +54,18 @@ pub async fn get_db() -> anyhow::Result<&'static DatabaseConnection> {
     })
     .await
 }
+
+/// Checks that the database still answers and records the result in its health. Does nothing
+/// before the first connection, which `get_db` reports itself.
+pub async fn check() {
+    let db = match DB.get() {
+        Some(db) => db,
+        None => return,
+    };
+    let statement = Statement::from_string(db.get_database_backend(), "SELECT 1".to_string());
+    match timeout(CHECK_TIMEOUT, db.execute(statement)).await {
+        Ok(Ok(_)) => health::set_up(COMPONENT),
+        Ok(Err(err)) => health::set_down(COMPONENT, err, &config::get().reconnect),
+        Err(_) => health::set_down(COMPONENT, "timed out", &config::get().reconnect),
+    }
+}
@@ -1 +1,4 @@
+pub mod job_run;
+pub mod user;
+pub mod yakudo_image;
 pub mod yakudo_score;
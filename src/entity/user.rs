@@ -0,0 +1,23 @@
+use sea_orm::entity::prelude::*;
+
+/// The latest known profile of a user who posted a yakudo.
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel)]
+#[sea_orm(table_name = "users")]
+pub struct Model {
+    /// The ID of the user on the bot's instance.
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub id: String,
+    pub username: String,
+    /// `None` for local users.
+    pub host: Option<String>,
+    /// The display name.
+    pub name: Option<String>,
+    #[sea_orm(column_type = "Text", nullable)]
+    pub avatar_url: Option<String>,
+    pub updated_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,39 @@
+use sea_orm::entity::prelude::*;
+
+/// An attachment of a scored note.
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel)]
+#[sea_orm(table_name = "yakudo_images")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i32,
+    pub yakudo_score_id: i32,
+    /// The drive file ID.
+    pub file_id: String,
+    #[sea_orm(column_type = "Text")]
+    pub url: String,
+    pub mime: String,
+    pub width: Option<i32>,
+    pub height: Option<i32>,
+    /// The score of this attachment alone. Frames of animations and videos are aggregated.
+    pub score: f64,
+    pub scorer_version: String,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::yakudo_score::Entity",
+        from = "Column::YakudoScoreId",
+        to = "super::yakudo_score::Column::Id",
+        on_delete = "Cascade"
+    )]
+    YakudoScore,
+}
+
+impl Related<super::yakudo_score::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::YakudoScore.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
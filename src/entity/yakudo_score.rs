@@ -6,13 +6,54 @@ pub struct Model {
     #[sea_orm(primary_key)]
     pub id: i32,
     pub username: String,
+    /// The ID of the user on the bot's instance. `None` for records made before it was stored.
+    pub user_id: Option<String>,
+    /// `None` for local users, and for records made before it was stored.
+    pub user_host: Option<String>,
+    #[sea_orm(unique)]
     pub note_id: String,
     pub quote_id: String,
+    /// The aggregate of the scores of the attachments, as shown in the quote.
     pub score: f64,
     pub date: chrono::DateTime<chrono::Local>,
+    pub verdict: Option<String>,
+    pub normalize_long_edge: Option<i32>,
+    pub normalize_denoise_sigma: Option<f64>,
+    pub hashtag: Option<String>,
+    /// How the scores of the attachments were combined into `score`.
+    pub aggregate: Option<String>,
+    /// The scorer and its version, e.g. `laplacian:1`.
+    pub scorer_version: Option<String>,
+    /// When the record was made. Unlike `date`, this is stored in UTC.
+    pub created_at: Option<chrono::DateTime<chrono::Utc>>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
-pub enum Relation {}
+pub enum Relation {
+    #[sea_orm(has_many = "super::yakudo_image::Entity")]
+    YakudoImage,
+}
+
+impl Related<super::yakudo_image::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::YakudoImage.def()
+    }
+}
 
 impl ActiveModelBehavior for ActiveModel {}
+
+impl Model {
+    /// Tells users apart. Records made before user IDs were stored fall back to the username
+    /// unless `users::resolve_legacy` found the ID of the local user with it.
+    pub fn user_key(&self) -> String {
+        match &self.user_id {
+            Some(user_id) => user_id.clone(),
+            None => format!("@{}", self.username),
+        }
+    }
+
+    /// `username` for local users and `username@host` for remote ones.
+    pub fn acct(&self) -> String {
+        crate::users::acct(&self.username, self.user_host.as_deref())
+    }
+}
@@ -1,31 +1,60 @@
-use std::{sync::Arc, time::Duration};
+use std::sync::Arc;
 
-use crate::misskey::Misskey;
-use futures::{StreamExt, TryStreamExt};
+use crate::{
+    command, config,
+    health::{self, Backoff, Connection},
+    metrics,
+    misskey::Misskey,
+};
+use anyhow::Context;
+use futures::TryStreamExt;
 use misskey::{streaming::channel::main::MainStreamEvent, ClientExt, StreamingClientExt};
 use tokio::time::sleep;
 
+const COMPONENT: &str = "main_stream";
+
 pub async fn monitor_follower(misskey: Arc<Misskey>) -> anyhow::Result<()> {
-    'retry: loop {
-        let stream_client = misskey.stream().await?;
-        let mut stream = stream_client.main_stream().await?;
-
-        while let Some(next) = stream.next().await {
-            match next {
-                Ok(MainStreamEvent::Followed(user)) => {
-                    if let Err(err) = misskey.follow(&user).await {
-                        warn!("failed to follow user: {}", err);
-                    }
+    let policy = config::get().reconnect;
+    let mut backoff = Backoff::new(policy);
+    health::register(COMPONENT);
+
+    loop {
+        match stream_main(&misskey, &mut backoff).await {
+            Ok(()) => health::set_down(COMPONENT, "stream closed", &policy),
+            Err(err) => health::set_down(COMPONENT, format!("{:#}", err), &policy),
+        }
+        let delay = backoff.next_delay();
+        warn!(
+            "reconnecting to the main stream in {:.1} seconds...",
+            delay.as_secs_f64()
+        );
+        sleep(delay).await;
+    }
+}
+
+/// Handles the events of the main stream until it fails or is closed.
+async fn stream_main(misskey: &Arc<Misskey>, backoff: &mut Backoff) -> anyhow::Result<()> {
+    let stream_client = misskey.stream().await?;
+    let mut stream = stream_client.main_stream().await?;
+    let mut connection = Connection::new(COMPONENT, backoff);
+
+    while let Some(next) = connection.next(&mut stream).await {
+        match next.context("error while streaming events")? {
+            MainStreamEvent::Followed(user) => {
+                if let Err(err) = metrics::api_result("follow", misskey.follow(&user).await) {
+                    warn!("failed to follow user: {}", err);
                 }
-                Ok(_) => {}
-                Err(e) => {
-                    warn!("error while streaming notes: {}. retrying...", e);
-                    sleep(Duration::from_secs(5)).await;
-                    continue 'retry;
+            }
+            MainStreamEvent::Mention(note) => {
+                if let Err(err) = command::process_mention(misskey.clone(), note).await {
+                    warn!("error while processing command: {}", err);
                 }
             }
+            _ => {}
         }
     }
+
+    Ok(())
 }
 
 pub async fn follow_followers(misskey: Arc<Misskey>) -> anyhow::Result<()> {
@@ -46,7 +75,7 @@ pub async fn follow_followers(misskey: Arc<Misskey>) -> anyhow::Result<()> {
             continue;
         }
         info!("following: {}", follower.username);
-        if let Err(err) = misskey.follow(&follower).await {
+        if let Err(err) = metrics::api_result("follow", misskey.follow(&follower).await) {
             warn!("failed to follow user: {}", err);
         }
     }
@@ -0,0 +1,202 @@
+use std::{
+    collections::BTreeMap,
+    fmt::Display,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use chrono::{DateTime, Utc};
+use futures::{Stream, StreamExt};
+use rand::Rng;
+use serde::Deserialize;
+
+/// How long a new connection must stay open to count as up when nothing is received on it.
+const STABLE_AFTER: Duration = Duration::from_secs(30);
+
+/// How long to wait before reconnecting to the streams and the database.
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ReconnectPolicy {
+    /// The delay before the first retry in seconds. It doubles on each failure in a row.
+    pub initial_delay: f64,
+    /// The upper bound of the delay in seconds.
+    pub max_delay: f64,
+    /// The number of attempts to connect to the database before giving up.
+    pub database_attempts: usize,
+    /// The number of failures in a row after which they are logged as errors.
+    pub alert_after: u32,
+}
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        Self {
+            initial_delay: 1.0,
+            max_delay: 300.0,
+            database_attempts: 10,
+            alert_after: 5,
+        }
+    }
+}
+
+/// Exponential backoff with equal jitter: the delay is drawn from `[d/2, d]` where `d` doubles on
+/// each failure, so that clients cut off together don't all reconnect at once.
+pub struct Backoff {
+    policy: ReconnectPolicy,
+    failures: u32,
+}
+impl Backoff {
+    pub fn new(policy: ReconnectPolicy) -> Self {
+        Self {
+            policy,
+            failures: 0,
+        }
+    }
+
+    /// Returns the delay before the next attempt and counts the failure.
+    pub fn next_delay(&mut self) -> Duration {
+        let delay = (self.policy.initial_delay * 2f64.powi(self.failures.min(30) as i32))
+            .min(self.policy.max_delay);
+        self.failures += 1;
+        Duration::from_secs_f64(rand::thread_rng().gen_range(delay / 2.0..=delay))
+    }
+
+    pub fn reset(&mut self) {
+        self.failures = 0;
+    }
+}
+
+/// A new stream connection of a component. It counts as up, resetting the backoff and the
+/// failures in a row, only once it has received an event or stayed open for `STABLE_AFTER`, so
+/// that a server dropping connections right after accepting them still backs off and alerts.
+pub struct Connection<'a> {
+    component: &'a str,
+    backoff: &'a mut Backoff,
+    /// `None` once the connection is up.
+    stable_at: Option<Instant>,
+}
+impl<'a> Connection<'a> {
+    pub fn new(component: &'a str, backoff: &'a mut Backoff) -> Self {
+        Self {
+            component,
+            backoff,
+            stable_at: Some(Instant::now() + STABLE_AFTER),
+        }
+    }
+
+    /// Returns the next event of `stream`, marking the connection as up on the way.
+    pub async fn next<S, T, E>(&mut self, stream: &mut S) -> Option<Result<T, E>>
+    where
+        S: Stream<Item = Result<T, E>> + Unpin,
+    {
+        if let Some(stable_at) = self.stable_at {
+            // the stream is polled at least once even if the time is already up, so that a
+            // connection that died meanwhile is not taken for a stable one
+            let wait = stable_at.saturating_duration_since(Instant::now());
+            match tokio::time::timeout(wait, stream.next()).await {
+                Ok(next) => {
+                    if let Some(Ok(_)) = next {
+                        self.set_up();
+                    }
+                    return next;
+                }
+                Err(_) => self.set_up(),
+            }
+        }
+        stream.next().await
+    }
+
+    fn set_up(&mut self) {
+        set_up(self.component);
+        self.backoff.reset();
+        self.stable_at = None;
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ComponentHealth {
+    pub up: bool,
+    /// Since when the component is up or down.
+    pub since: DateTime<Utc>,
+    /// The number of times the component went down after being up.
+    pub reconnects: u64,
+    /// The number of failed attempts since the component was last up.
+    pub failures: u32,
+    /// The last failure, also after the component came back up. Shown by `/readyz`.
+    pub last_error: Option<String>,
+}
+impl ComponentHealth {
+    fn new() -> Self {
+        Self {
+            up: false,
+            since: Utc::now(),
+            reconnects: 0,
+            failures: 0,
+            last_error: None,
+        }
+    }
+}
+
+/// The connection state of each component, e.g. `database` or `stream:#mis1yakudo`.
+static HEALTH: Mutex<BTreeMap<String, ComponentHealth>> = Mutex::new(BTreeMap::new());
+
+/// Registers a component as not connected yet, so that it is reported before its first attempt.
+pub fn register(component: &str) {
+    HEALTH
+        .lock()
+        .unwrap()
+        .entry(component.to_string())
+        .or_insert_with(ComponentHealth::new);
+}
+
+pub fn set_up(component: &str) {
+    let mut health = HEALTH.lock().unwrap();
+    let state = health
+        .entry(component.to_string())
+        .or_insert_with(ComponentHealth::new);
+    if !state.up {
+        if state.failures > 0 {
+            info!(
+                "{} is up again after {} failure(s) since {}",
+                component, state.failures, state.since
+            );
+        }
+        state.up = true;
+        state.since = Utc::now();
+        state.failures = 0;
+    }
+}
+
+/// Records a failure of `component`. Failures are logged as errors once there have been
+/// `policy.alert_after` of them in a row.
+pub fn set_down(component: &str, err: impl Display, policy: &ReconnectPolicy) {
+    let mut health = HEALTH.lock().unwrap();
+    let state = health
+        .entry(component.to_string())
+        .or_insert_with(ComponentHealth::new);
+    if state.up {
+        state.up = false;
+        state.since = Utc::now();
+        state.reconnects += 1;
+    }
+    state.failures += 1;
+    state.last_error = Some(err.to_string());
+
+    if state.failures >= policy.alert_after {
+        error!(
+            "{} has been down since {} ({} failures in a row): {}",
+            component, state.since, state.failures, err
+        );
+    } else {
+        warn!("{} is down: {}", component, err);
+    }
+}
+
+pub fn snapshot() -> BTreeMap<String, ComponentHealth> {
+    HEALTH.lock().unwrap().clone()
+}
+
+/// Whether every registered component is up. Nothing is healthy before the first component is
+/// registered.
+pub fn is_healthy() -> bool {
+    let health = HEALTH.lock().unwrap();
+    !health.is_empty() && health.values().all(|state| state.up)
+}
@@ -5,17 +5,61 @@ use crate::scheduler::start_scheduler;
 #[macro_use]
 extern crate log;
 
+mod aggregate;
+mod animation;
+mod cli;
+mod command;
+mod config;
 mod database;
 mod entity;
 mod follow;
+mod health;
+mod metrics;
 mod misskey;
 mod monitor;
+mod report;
 mod scheduler;
+mod scorer;
+mod stats;
+mod users;
+mod verdict;
+mod video;
+mod web;
 
 #[tokio::main]
 async fn main() {
     pretty_env_logger::init();
 
+    let config = match config::init() {
+        Ok(config) => config,
+        Err(e) => {
+            error!("failed to load config: {:#}", e);
+            std::process::exit(1);
+        }
+    };
+
+    let args = std::env::args().collect::<Vec<_>>();
+    if args.get(1).map(String::as_str) == Some("score") {
+        if let Err(err) = cli::score(&args[2..]).await {
+            error!("{:#}", err);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    if let Err(e) = config.validate_bot() {
+        error!("invalid config: {:#}", e);
+        std::process::exit(1);
+    }
+
+    if config.http.enabled {
+        tokio::spawn(async move {
+            if let Err(err) = metrics::serve(&config.http).await {
+                error!("failed to serve health and metrics: {:#}", err);
+            }
+        });
+    }
+
     let misskey = match misskey::Misskey::new().await {
         Ok(misskey) => misskey,
         Err(e) => {
@@ -26,6 +70,13 @@ async fn main() {
 
     let misskey = Arc::new(misskey);
 
+    let misskey_clone = misskey.clone();
+    tokio::spawn(async move {
+        if let Err(err) = users::backfill_yakudo_users(misskey_clone).await {
+            error!("failed to backfill the users of yakudos: {:#}", err);
+        }
+    });
+
     let misskey_clone = misskey.clone();
     if let Err(err) = start_scheduler(misskey_clone).await {
         error!("failed to start scheduler: {:#}", err);
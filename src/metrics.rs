@@ -0,0 +1,202 @@
+use std::{convert::Infallible, net::SocketAddr, sync::OnceLock};
+
+use hyper::{
+    header::CONTENT_TYPE,
+    service::{make_service_fn, service_fn},
+    Body, Method, Request, Response, Server, StatusCode,
+};
+use prometheus::{
+    exponential_buckets, Encoder, HistogramOpts, HistogramVec, IntCounterVec, IntGaugeVec, Opts,
+    Registry, TextEncoder,
+};
+use serde::Deserialize;
+
+use crate::{config, database, health, web};
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HttpConfig {
+    /// Whether to serve `/healthz`, `/readyz` and `/metrics`.
+    pub enabled: bool,
+    pub listen: SocketAddr,
+    /// Whether to also serve the leaderboard pages and their JSON API.
+    pub web: bool,
+}
+impl Default for HttpConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            listen: SocketAddr::from(([0, 0, 0, 0], 8080)),
+            web: true,
+        }
+    }
+}
+
+pub struct Metrics {
+    registry: Registry,
+    /// By hashtag and outcome: `scored`, `no_image`, `video_rejected` or `error`.
+    pub notes_processed: IntCounterVec,
+    /// The final score of each scored note by hashtag.
+    pub scores: HistogramVec,
+    /// The time to download and score one attachment, by `image` or `video`.
+    pub scoring_seconds: HistogramVec,
+    /// Failed Misskey API calls by operation.
+    pub api_errors: IntCounterVec,
+    /// Finished scheduled jobs by job and outcome: `success` or `failure`.
+    pub jobs: IntCounterVec,
+    component_up: IntGaugeVec,
+    component_reconnects: IntCounterVec,
+}
+
+static METRICS: OnceLock<Metrics> = OnceLock::new();
+
+pub fn get() -> &'static Metrics {
+    METRICS.get_or_init(|| Metrics::new().expect("failed to create metrics"))
+}
+
+impl Metrics {
+    fn new() -> anyhow::Result<Self> {
+        let registry = Registry::new_custom(Some("yakudobot".to_string()), None)?;
+
+        let notes_processed = IntCounterVec::new(
+            Opts::new("notes_processed_total", "Notes processed"),
+            &["hashtag", "outcome"],
+        )?;
+        let scores = HistogramVec::new(
+            HistogramOpts::new("score", "Final yakudo scores of notes")
+                .buckets(exponential_buckets(1.0, 2.0, 12)?),
+            &["hashtag"],
+        )?;
+        let scoring_seconds = HistogramVec::new(
+            HistogramOpts::new(
+                "scoring_seconds",
+                "Time to download and score an attachment",
+            )
+            .buckets(exponential_buckets(0.05, 2.0, 10)?),
+            &["kind"],
+        )?;
+        let api_errors = IntCounterVec::new(
+            Opts::new("api_errors_total", "Failed Misskey API calls"),
+            &["operation"],
+        )?;
+        let jobs = IntCounterVec::new(
+            Opts::new("jobs_total", "Finished scheduled jobs"),
+            &["job", "outcome"],
+        )?;
+        let component_up = IntGaugeVec::new(
+            Opts::new("component_up", "Whether the connection is up"),
+            &["component"],
+        )?;
+        let component_reconnects = IntCounterVec::new(
+            Opts::new(
+                "component_reconnects_total",
+                "Times the connection went down after being up",
+            ),
+            &["component"],
+        )?;
+
+        registry.register(Box::new(notes_processed.clone()))?;
+        registry.register(Box::new(scores.clone()))?;
+        registry.register(Box::new(scoring_seconds.clone()))?;
+        registry.register(Box::new(api_errors.clone()))?;
+        registry.register(Box::new(jobs.clone()))?;
+        registry.register(Box::new(component_up.clone()))?;
+        registry.register(Box::new(component_reconnects.clone()))?;
+
+        Ok(Self {
+            registry,
+            notes_processed,
+            scores,
+            scoring_seconds,
+            api_errors,
+            jobs,
+            component_up,
+            component_reconnects,
+        })
+    }
+
+    /// Encodes the metrics in the Prometheus text format.
+    fn render(&self) -> anyhow::Result<String> {
+        for (component, state) in health::snapshot() {
+            self.component_up
+                .with_label_values(&[&component])
+                .set(state.up as i64);
+            // the health registry keeps the count, so the counter only catches up with it
+            let reconnects = self.component_reconnects.with_label_values(&[&component]);
+            reconnects.inc_by(state.reconnects.saturating_sub(reconnects.get()));
+        }
+
+        let mut buffer = vec![];
+        TextEncoder::new().encode(&self.registry.gather(), &mut buffer)?;
+        Ok(String::from_utf8(buffer)?)
+    }
+}
+
+/// Counts a failed Misskey API call and passes the result through.
+pub fn api_result<T, E>(operation: &str, result: Result<T, E>) -> Result<T, E> {
+    if result.is_err() {
+        get().api_errors.with_label_values(&[operation]).inc();
+    }
+    result
+}
+
+/// Serves `/healthz` (the process is alive), `/readyz` (the database and the streams are
+/// connected), `/metrics` and the leaderboard pages.
+pub async fn serve(config: &HttpConfig) -> anyhow::Result<()> {
+    let make_service = make_service_fn(|_| async { Ok::<_, Infallible>(service_fn(handle)) });
+    info!("serving health and metrics on http://{}", config.listen);
+    Server::try_bind(&config.listen)?
+        .serve(make_service)
+        .await?;
+    Ok(())
+}
+
+async fn handle(request: Request<Body>) -> Result<Response<Body>, Infallible> {
+    let response = match (request.method(), request.uri().path()) {
+        (&Method::GET, "/healthz") => text(StatusCode::OK, "ok\n".to_string()),
+        (&Method::GET, "/readyz") => {
+            database::check().await;
+            let body = health::snapshot()
+                .iter()
+                .map(|(component, state)| {
+                    let last_error = state
+                        .last_error
+                        .as_ref()
+                        .map(|err| format!(" (last error: {})", err))
+                        .unwrap_or_default();
+                    format!(
+                        "{} {}{}\n",
+                        component,
+                        if state.up { "up" } else { "down" },
+                        last_error
+                    )
+                })
+                .collect::<String>();
+            let status = if health::is_healthy() {
+                StatusCode::OK
+            } else {
+                StatusCode::SERVICE_UNAVAILABLE
+            };
+            text(status, body)
+        }
+        (&Method::GET, "/metrics") => match get().render() {
+            Ok(body) => text(StatusCode::OK, body),
+            Err(err) => {
+                error!("failed to render metrics: {:#}", err);
+                text(StatusCode::INTERNAL_SERVER_ERROR, String::new())
+            }
+        },
+        _ if config::get().http.web => web::handle(request.method(), request.uri()).await,
+        _ => text(StatusCode::NOT_FOUND, String::new()),
+    };
+    Ok(response)
+}
+
+fn text(status: StatusCode, body: String) -> Response<Body> {
+    let mut response = Response::new(Body::from(body));
+    *response.status_mut() = status;
+    response
+        .headers_mut()
+        .insert(CONTENT_TYPE, "text/plain; version=0.0.4".parse().unwrap());
+    response
+}
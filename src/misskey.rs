@@ -1,6 +1,6 @@
 use std::ops::Deref;
 
-use anyhow::Context;
+use crate::config;
 use misskey::{
     model::{id::Id, note::Note, user::User},
     ClientExt, HttpClient, WebSocketClient,
@@ -17,11 +17,10 @@ impl Misskey {
     pub async fn new() -> anyhow::Result<Misskey> {
         info!("initializing misskey client...");
 
-        let instance = std::env::var("INSTANCE").context("INSTANCE is not set")?;
-        let token = std::env::var("TOKEN").context("TOKEN is not set")?;
-        let secure = std::env::var("SECURE")
-            .unwrap_or_else(|_| "true".to_string())
-            .parse::<bool>()?;
+        let config = &config::get().misskey;
+        let instance = config.instance.clone();
+        let token = config.token.clone();
+        let secure = config.secure;
 
         let api_endpoint = if secure {
             format!("https://{}/api/", instance)
@@ -56,12 +55,8 @@ impl Misskey {
             .await?)
     }
 
-    pub fn get_note_url(&self, note: &Note) -> String {
-        if self.secure {
-            format!("https://{}/notes/{}", self.instance, note.id)
-        } else {
-            format!("http://{}/notes/{}", self.instance, note.id)
-        }
+    pub fn get_note_url(&self, note_id: Id<Note>) -> String {
+        note_url(&note_id.to_string())
     }
 
     pub fn user_id(&self) -> Id<User> {
@@ -69,6 +64,17 @@ impl Misskey {
     }
 }
 
+/// The URL of a note on the configured instance. Unlike `Misskey::get_note_url`, this works
+/// without a client, e.g. for the web pages.
+pub fn note_url(note_id: &str) -> String {
+    let config = &config::get().misskey;
+    if config.secure {
+        format!("https://{}/notes/{}", config.instance, note_id)
+    } else {
+        format!("http://{}/notes/{}", config.instance, note_id)
+    }
+}
+
 impl Deref for Misskey {
     type Target = HttpClient;
 
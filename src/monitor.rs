@@ -1,53 +1,218 @@
-use std::{sync::Arc, time::Duration};
+use std::{
+    collections::BTreeSet,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
 
-use crate::{database::get_db, entity, misskey::Misskey};
+use crate::{
+    config::{self, HashtagConfig},
+    database::get_db,
+    entity,
+    health::{self, Backoff, Connection},
+    metrics,
+    misskey::Misskey,
+    scorer::{ImageScore, Pipeline},
+    stats,
+    users::{self, UserKey},
+    video::{self, VideoScore},
+};
 use anyhow::Context;
-use futures::StreamExt;
-use migration::sea_orm::{ActiveModelTrait, ActiveValue};
-use misskey::{model::note::Note, ClientExt, StreamingClientExt};
-use opencv::prelude::*;
+use migration::sea_orm::{
+    ActiveModelTrait, ActiveValue, ColumnTrait, EntityTrait, QueryFilter, QueryOrder,
+    TransactionTrait,
+};
+use misskey::{
+    endpoint::notes::search_by_tag,
+    model::{
+        drive::DriveFile,
+        id::Id,
+        note::{Note, Tag},
+        query::Query,
+    },
+    Client, ClientExt, StreamingClientExt,
+};
 use reqwest::Url;
 use tokio::time::sleep;
 
-#[cfg(debug_assertions)]
-const SEARCH_HASHTAG: &str = "mis1yakudotest";
-#[cfg(not(debug_assertions))]
-const SEARCH_HASHTAG: &str = "mis1yakudo";
+/// The number of notes fetched at once while backfilling.
+const BACKFILL_PAGE_SIZE: u8 = 100;
+
+/// The hashtags whose first backfill since startup is over, whether it succeeded or not.
+static BACKFILLED: Mutex<BTreeSet<String>> = Mutex::new(BTreeSet::new());
 
 pub async fn monitor_notes(misskey: Arc<Misskey>) -> anyhow::Result<()> {
-    'retry: loop {
-        let stream_client = misskey.stream().await?;
-        let mut stream = stream_client.hashtag_timeline(SEARCH_HASHTAG).await?;
-        info!("Start monitoring notes with hashtag: #{:?}", SEARCH_HASHTAG);
-
-        while let Some(next) = stream.next().await {
-            match next {
-                Ok(note) => {
-                    if let Err(err) = process_note(misskey.clone(), note).await {
-                        warn!("error while processing note: {}. retrying...", err);
-                    }
-                }
-                Err(e) => {
-                    warn!("error while streaming notes: {}. retrying...", e);
-                    sleep(Duration::from_secs(5)).await;
-                    continue 'retry;
-                }
+    let config = config::get();
+    let pipeline = Arc::new(Pipeline::from_config(&config.scoring)?);
+    info!(
+        "using scorer: {}, normalization: {:?}, video: {:?}",
+        pipeline.scorer.name(),
+        pipeline.normalization,
+        pipeline.video
+    );
+
+    let monitors = config.hashtags.iter().map(|hashtag| {
+        let handle = tokio::spawn(monitor_hashtag(misskey.clone(), pipeline.clone(), hashtag));
+        async move { handle.await? }
+    });
+    futures::future::try_join_all(monitors).await?;
+
+    Ok(())
+}
+
+async fn monitor_hashtag(
+    misskey: Arc<Misskey>,
+    pipeline: Arc<Pipeline>,
+    hashtag: &'static HashtagConfig,
+) -> anyhow::Result<()> {
+    let policy = config::get().reconnect;
+    let mut backoff = Backoff::new(policy);
+    let component = format!("stream:#{}", hashtag.name);
+    health::register(&component);
+
+    loop {
+        let result = stream_hashtag(&misskey, &pipeline, hashtag, &component, &mut backoff).await;
+        match result {
+            Ok(()) => health::set_down(&component, "stream closed", &policy),
+            Err(err) => health::set_down(&component, format!("{:#}", err), &policy),
+        }
+        let delay = backoff.next_delay();
+        warn!(
+            "reconnecting to #{} in {:.1} seconds...",
+            hashtag.name,
+            delay.as_secs_f64()
+        );
+        sleep(delay).await;
+    }
+}
+
+/// Streams the notes of `hashtag` until the stream fails or is closed.
+async fn stream_hashtag(
+    misskey: &Arc<Misskey>,
+    pipeline: &Arc<Pipeline>,
+    hashtag: &'static HashtagConfig,
+    component: &str,
+    backoff: &mut Backoff,
+) -> anyhow::Result<()> {
+    let stream_client = misskey.stream().await?;
+    let mut stream = stream_client.hashtag_timeline(&*hashtag.name).await?;
+    info!("Start monitoring notes with hashtag: #{:?}", hashtag.name);
+    let mut connection = Connection::new(component, backoff);
+
+    // the notes streamed meanwhile are buffered, so nothing falls in between
+    if let Err(err) = backfill(misskey, pipeline, hashtag).await {
+        warn!("error while backfilling notes: {}", err);
+    }
+    BACKFILLED.lock().unwrap().insert(hashtag.name.clone());
+
+    while let Some(next) = connection.next(&mut stream).await {
+        let note = next.context("error while streaming notes")?;
+        if let Err(err) = process_note(misskey.clone(), pipeline.clone(), hashtag, note).await {
+            record_error(hashtag, err);
+        }
+    }
+
+    Ok(())
+}
+
+/// Processes the notes posted since the last processed note, which were missed while the stream
+/// was disconnected or the bot was down.
+async fn backfill(
+    misskey: &Arc<Misskey>,
+    pipeline: &Arc<Pipeline>,
+    hashtag: &'static HashtagConfig,
+) -> anyhow::Result<()> {
+    // the newest note rather than the latest record, which may be an old note rescored or the
+    // parent of a late reply. Misskey IDs sort by the time of the note
+    let last_processed = entity::yakudo_score::Entity::find()
+        .filter(entity::yakudo_score::Column::Hashtag.eq(&*hashtag.name))
+        .order_by_desc(entity::yakudo_score::Column::NoteId)
+        .one(get_db().await?)
+        .await
+        .context("failed to get the last processed note")?;
+    let mut since_id = match last_processed {
+        Some(yakudo) => yakudo.note_id.parse::<Id<Note>>()?,
+        // nothing to catch up on for a new hashtag
+        None => return Ok(()),
+    };
+    info!("backfilling notes of #{} since {}", hashtag.name, since_id);
+
+    loop {
+        let result = misskey
+            .request(search_by_tag::Request {
+                query: Query::atom(Tag(hashtag.name.clone())),
+                since_id: Some(since_id),
+                limit: Some(BACKFILL_PAGE_SIZE),
+                ..Default::default()
+            })
+            .await
+            .map_err(anyhow::Error::from)
+            .and_then(|response| Ok(response.into_result()?));
+        let mut notes = metrics::api_result("search_by_tag", result)?;
+        if notes.is_empty() {
+            break;
+        }
+        // oldest first
+        notes.sort_by_key(|note| note.created_at);
+        since_id = notes.last().unwrap().id;
+
+        for note in notes {
+            info!("backfilling note: {}", note.id);
+            if let Err(err) = process_note(misskey.clone(), pipeline.clone(), hashtag, note).await {
+                record_error(hashtag, err);
             }
         }
     }
+
+    info!("backfilled notes of #{}", hashtag.name);
+    Ok(())
+}
+
+/// Waits until every hashtag has been backfilled once since startup, or `timeout` has passed,
+/// so that reports caught up on at startup include the notes posted while the bot was down.
+pub async fn wait_for_backfill(timeout: Duration) {
+    let start = Instant::now();
+    while start.elapsed() < timeout {
+        let backfilled = BACKFILLED.lock().unwrap().clone();
+        if config::get()
+            .hashtags
+            .iter()
+            .all(|h| backfilled.contains(&h.name))
+        {
+            return;
+        }
+        sleep(Duration::from_secs(1)).await;
+    }
+    warn!(
+        "backfill did not finish in {:?}. going on without it",
+        timeout
+    );
 }
 
 #[async_recursion::async_recursion]
-async fn process_note(misskey: Arc<Misskey>, note: Note) -> anyhow::Result<()> {
+pub async fn process_note(
+    misskey: Arc<Misskey>,
+    pipeline: Arc<Pipeline>,
+    hashtag: &'static HashtagConfig,
+    note: Note,
+) -> anyhow::Result<()> {
+    if let Some(owner) = note
+        .text
+        .as_deref()
+        .and_then(|text| config::get().hashtag_of(text))
+    {
+        if owner.name != hashtag.name {
+            info!("note belongs to #{}. skipping...", owner.name);
+            return Ok(());
+        }
+    }
+
     if let Some(reply_id) = &note.reply_id {
-        let note = misskey
-            .get_note(*reply_id)
-            .await
+        let note = metrics::api_result("get_note", misskey.get_note(*reply_id).await)
             .context("failed to get the note that this note is replying to")?;
-        return process_note(misskey, note).await;
+        return process_note(misskey, pipeline, hashtag, note).await;
     }
 
-    let note_url = misskey.get_note_url(&note);
+    let note_url = misskey.get_note_url(note.id);
     info!("note: {}", note_url);
 
     if note.user.id == misskey.user_id() || note.renote_id.is_some() {
@@ -55,32 +220,115 @@ async fn process_note(misskey: Arc<Misskey>, note: Note) -> anyhow::Result<()> {
         return Ok(());
     }
 
+    // a note can arrive twice, e.g. after a reconnect or as the parent of several replies
+    let _in_flight = match InFlight::claim(&note) {
+        Some(in_flight) => in_flight,
+        None => {
+            info!("note is already being processed. skipping...");
+            return Ok(());
+        }
+    };
+    if is_processed(&note).await? {
+        info!("note is already processed. skipping...");
+        return Ok(());
+    }
+
+    score_note(misskey, pipeline, hashtag, note, vec![]).await
+}
+
+/// Scores a note that was processed before again. `old` (the records of the note and their
+/// quotes) is replaced only once the new record is saved, so a failure leaves it untouched.
+pub async fn rescore_note(
+    misskey: Arc<Misskey>,
+    pipeline: Arc<Pipeline>,
+    hashtag: &'static HashtagConfig,
+    note: Note,
+    old: Vec<entity::yakudo_score::Model>,
+) -> anyhow::Result<()> {
+    let _in_flight = InFlight::claim(&note)
+        .ok_or_else(|| anyhow::anyhow!("note {} is already being processed", note.id))?;
+    score_note(misskey, pipeline, hashtag, note, old).await
+}
+
+/// Scores, quotes and records a note, replacing the records in `old`.
+async fn score_note(
+    misskey: Arc<Misskey>,
+    pipeline: Arc<Pipeline>,
+    hashtag: &'static HashtagConfig,
+    note: Note,
+    old: Vec<entity::yakudo_score::Model>,
+) -> anyhow::Result<()> {
+    let note_url = misskey.get_note_url(note.id);
     info!("note: {:?}", note);
 
-    let mut message = String::new();
-    message.push_str(&chrono::Local::now().format("%Y-%m-%d %H:%M").to_string());
-    message.push_str("\nUser:@");
-    message.push_str(&note.user.username);
-    if let Some(host) = &note.user.host {
-        message.push('@');
-        message.push_str(host);
+    let config = config::get();
+    let user_key = UserKey::from(&note.user);
+    let user = format!("@{}", user_key.acct());
+    if let Err(err) = users::remember(&note.user).await {
+        warn!("failed to cache user {}: {:#}", user, err);
     }
-    message.push('\n');
 
+    let mut details = String::new();
+    let mut verdict_message = String::new();
+    let mut score_text = "-inf".to_string();
     let mut yakudo_score: f64 = 0.0;
+    let mut rank = None;
+    let mut new_personal_best = false;
+    let mut outcome = "no_image";
+    let mut scored = None;
+    let mut images = vec![];
+    let mut aggregate = None;
 
     if note.files.is_empty() {
-        message.push_str("画像が入ってないやん!\nScore:-inf\n");
+        verdict_message = config.messages.no_image.clone();
         info!("no photo found in note. aborting...");
     } else {
-        let mut final_score = 0.0;
+        let mut scores = vec![];
         let mut count = 0;
         let mut is_photo = true;
         for file in &note.files {
             match file.type_.type_() {
+                mime::VIDEO if pipeline.video.enabled => {
+                    let url = if let Some(url) = &file.url {
+                        url
+                    } else {
+                        info!("file url not found. skipping...");
+                        continue;
+                    };
+                    info!("calculating yakudo score for video: {}", url);
+
+                    let _timer = metrics::get()
+                        .scoring_seconds
+                        .with_label_values(&["video"])
+                        .start_timer();
+                    let video_score = calc_video_yakudo_score(&pipeline, url).await?;
+                    let score = pipeline.frame_aggregate.apply(&video_score.scores());
+                    scores.push(score);
+                    count += 1;
+                    details.push_str(&format!(
+                        "{}枚目(動画):{:.3} [{}] (最高{:.3} 平均{:.3} {}フレーム)\n",
+                        count,
+                        score,
+                        pipeline.frame_aggregate,
+                        video_score.best(),
+                        video_score.mean(),
+                        video_score.frames.len()
+                    ));
+                    images.push(image_record(
+                        &pipeline,
+                        file,
+                        url,
+                        &video_score.frames[0],
+                        score,
+                    ));
+
+                    info!("calculated yakudo score for video {}: {}", count, score);
+                }
                 mime::VIDEO => {
-                    message.push_str("やめろ！クソ動画を投稿するんじゃない!\nScore:-inf\n");
-                    yakudo_score = 0.0;
+                    details.clear();
+                    verdict_message = config.messages.video_rejected.clone();
+                    outcome = "video_rejected";
+                    images.clear();
                     is_photo = false;
                     info!("video found in note. aborting...");
                     break;
@@ -94,11 +342,41 @@ async fn process_note(misskey: Arc<Misskey>, note: Note) -> anyhow::Result<()> {
                     };
                     info!("calculating yakudo score for image: {}", url);
 
-                    let score = calc_yakudo_score(url).await?;
-                    final_score += score;
+                    let _timer = metrics::get()
+                        .scoring_seconds
+                        .with_label_values(&["image"])
+                        .start_timer();
+                    let frame_scores = calc_yakudo_score(&pipeline, url).await?;
                     count += 1;
-                    message.push_str(&format!("{}枚目:{:.3}\n", count, score));
-                    yakudo_score = score;
+                    let score = if let [image_score] = frame_scores.as_slice() {
+                        details.push_str(&format!(
+                            "{}枚目:{:.3} ({}ブレ{:.0}° 異方性{:.2})\n",
+                            count,
+                            image_score.score,
+                            image_score.motion_blur.direction(),
+                            image_score.motion_blur.angle,
+                            image_score.motion_blur.anisotropy
+                        ));
+                        image_score.score
+                    } else {
+                        let scores = frame_scores.iter().map(|f| f.score).collect::<Vec<_>>();
+                        let score = pipeline.frame_aggregate.apply(&scores);
+                        details.push_str(&format!(
+                            "{}枚目(アニメーション):{:.3} [{}]\n",
+                            count, score, pipeline.frame_aggregate
+                        ));
+                        details.push_str(&format!(
+                            "  フレーム:{}\n",
+                            scores
+                                .iter()
+                                .map(|s| format!("{:.3}", s))
+                                .collect::<Vec<_>>()
+                                .join("/")
+                        ));
+                        score
+                    };
+                    scores.push(score);
+                    images.push(image_record(&pipeline, file, url, &frame_scores[0], score));
 
                     info!("calculated yakudo score for photo {}: {}", count, score);
                 }
@@ -108,71 +386,221 @@ async fn process_note(misskey: Arc<Misskey>, note: Note) -> anyhow::Result<()> {
                 }
             }
         }
-        if is_photo {
-            final_score /= count as f64;
-            if final_score >= 150.0 {
-                message.push_str("GoodYakudo!\n");
-            } else {
-                message.push_str("もっとyakudoしろ！\n");
+        if is_photo && count == 0 {
+            verdict_message = config.messages.no_image.clone();
+            info!("no photo could be scored. aborting...");
+        } else if is_photo {
+            let image_aggregate = hashtag.image_aggregate(config);
+            let final_score = image_aggregate.apply(&scores);
+            if scores.len() > 1 {
+                details.push_str(&format!("集計:{}\n", image_aggregate));
             }
-            message.push_str(&format!("Score:{:.3}\n", final_score));
+            yakudo_score = final_score;
+            aggregate = Some(image_aggregate.to_string());
+            let verdict = hashtag.verdicts(config).judge(final_score);
+            verdict_message = verdict.message.clone();
+            score_text = format!("{:.3}", final_score);
+            rank = Some(verdict.rank.clone());
+            outcome = "scored";
+            scored = Some(final_score);
+
+            let stats = stats::user_stats(&user_key, Some(&hashtag.name)).await?;
+            info!("stats of {} before this note: {:?}", user, stats);
+            new_personal_best = stats.best.map(|best| final_score > best).unwrap_or(false);
         }
     }
 
     info!("score: {}", yakudo_score);
 
+    let mut message = render_template(
+        hashtag.reply_template(config),
+        &[
+            (
+                "date",
+                &chrono::Local::now().format("%Y-%m-%d %H:%M").to_string(),
+            ),
+            ("hashtag", &hashtag.name),
+            ("user", &user),
+            ("details", &details),
+            ("verdict", &verdict_message),
+            ("rank", rank.as_deref().unwrap_or("-")),
+            ("score", &score_text),
+            ("aggregate", aggregate.as_deref().unwrap_or("-")),
+        ],
+    );
+    if new_personal_best {
+        message.push_str(&config.messages.personal_best);
+    }
+
     info!("noting: {}", message);
 
-    let response = misskey.quote(&note, message).await?;
+    let response = metrics::api_result("quote", misskey.quote(&note, message).await)?;
 
     let yakudo_score_entity = entity::yakudo_score::ActiveModel {
         username: ActiveValue::Set(note.user.username),
+        user_id: ActiveValue::Set(Some(note.user.id.to_string())),
+        user_host: ActiveValue::Set(note.user.host),
         note_id: ActiveValue::Set(note.id.to_string()),
         quote_id: ActiveValue::Set(response.id.to_string()),
         score: ActiveValue::Set(yakudo_score),
-        date: ActiveValue::Set(chrono::Local::now()),
+        // the time of the note rather than now, so that a note backfilled or rescored later
+        // still counts for the day it was posted
+        date: ActiveValue::Set(note.created_at.with_timezone(&chrono::Local)),
+        verdict: ActiveValue::Set(rank),
+        aggregate: ActiveValue::Set(aggregate),
+        scorer_version: ActiveValue::Set(Some(pipeline.scorer_version())),
+        created_at: ActiveValue::Set(Some(chrono::Utc::now())),
+        hashtag: ActiveValue::Set(Some(hashtag.name.clone())),
+        normalize_long_edge: ActiveValue::Set(pipeline.normalization.long_edge),
+        normalize_denoise_sigma: ActiveValue::Set(pipeline.normalization.denoise_sigma),
         ..Default::default()
     };
     info!("yakudo_score entity: {:#?}", yakudo_score_entity);
 
-    yakudo_score_entity.insert(get_db().await?).await?;
+    if let Err(err) = save_yakudo(yakudo_score_entity, images, &old).await {
+        // don't leave a quote without a record, which would be quoted again on the next try
+        if let Err(err) = metrics::api_result("delete_note", misskey.delete_note(response.id).await)
+        {
+            warn!("failed to delete quote: {}", err);
+        }
+        return Err(err);
+    }
+    for yakudo in old {
+        info!("deleting old quote {}", yakudo.quote_id);
+        let quote_id = yakudo.quote_id.parse::<Id<Note>>()?;
+        if let Err(err) = metrics::api_result("delete_note", misskey.delete_note(quote_id).await) {
+            // already deleted by hand
+            warn!("failed to delete old quote {}: {}", yakudo.quote_id, err);
+        }
+    }
+
+    let metrics = metrics::get();
+    metrics
+        .notes_processed
+        .with_label_values(&[&hashtag.name, outcome])
+        .inc();
+    if let Some(score) = scored.filter(|score| score.is_finite()) {
+        metrics
+            .scores
+            .with_label_values(&[&hashtag.name])
+            .observe(score);
+    }
 
     info!("finished processing note {}", note_url);
     Ok(())
 }
 
-async fn calc_yakudo_score(url: &Url) -> anyhow::Result<f64> {
+/// Inserts the record of a note together with the records of its attachments, replacing the
+/// records in `old`.
+async fn save_yakudo(
+    yakudo: entity::yakudo_score::ActiveModel,
+    images: Vec<entity::yakudo_image::ActiveModel>,
+    old: &[entity::yakudo_score::Model],
+) -> anyhow::Result<()> {
+    let txn = get_db().await?.begin().await?;
+    for yakudo in old {
+        info!("deleting old record {}", yakudo.id);
+        entity::yakudo_score::Entity::delete_by_id(yakudo.id)
+            .exec(&txn)
+            .await
+            .context("failed to delete old yakudo score")?;
+    }
+    let yakudo = yakudo
+        .insert(&txn)
+        .await
+        .context("failed to insert yakudo score")?;
+    for mut image in images {
+        image.yakudo_score_id = ActiveValue::Set(yakudo.id);
+        image
+            .insert(&txn)
+            .await
+            .context("failed to insert yakudo image")?;
+    }
+    txn.commit().await?;
+    Ok(())
+}
+
+fn image_record(
+    pipeline: &Pipeline,
+    file: &DriveFile,
+    url: &Url,
+    image: &ImageScore,
+    score: f64,
+) -> entity::yakudo_image::ActiveModel {
+    entity::yakudo_image::ActiveModel {
+        file_id: ActiveValue::Set(file.id.to_string()),
+        url: ActiveValue::Set(url.to_string()),
+        mime: ActiveValue::Set(file.type_.to_string()),
+        width: ActiveValue::Set(Some(image.width)),
+        height: ActiveValue::Set(Some(image.height)),
+        score: ActiveValue::Set(score),
+        scorer_version: ActiveValue::Set(pipeline.scorer_version()),
+        ..Default::default()
+    }
+}
+
+pub fn record_error(hashtag: &HashtagConfig, err: anyhow::Error) {
+    warn!("error while processing note: {}", err);
+    metrics::get()
+        .notes_processed
+        .with_label_values(&[&hashtag.name, "error"])
+        .inc();
+}
+
+/// The IDs of the notes being processed.
+static IN_FLIGHT: Mutex<BTreeSet<String>> = Mutex::new(BTreeSet::new());
+
+/// Marks a note as being processed until dropped.
+struct InFlight(String);
+impl InFlight {
+    /// Returns `None` if the note is already being processed.
+    fn claim(note: &Note) -> Option<Self> {
+        let note_id = note.id.to_string();
+        if IN_FLIGHT.lock().unwrap().insert(note_id.clone()) {
+            Some(InFlight(note_id))
+        } else {
+            None
+        }
+    }
+}
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        IN_FLIGHT.lock().unwrap().remove(&self.0);
+    }
+}
+
+async fn is_processed(note: &Note) -> anyhow::Result<bool> {
+    Ok(entity::yakudo_score::Entity::find()
+        .filter(entity::yakudo_score::Column::NoteId.eq(note.id.to_string()))
+        .one(get_db().await?)
+        .await
+        .context("failed to get yakudo score")?
+        .is_some())
+}
+
+/// Replaces each `{key}` in `template` with its value.
+fn render_template(template: &str, values: &[(&str, &str)]) -> String {
+    values
+        .iter()
+        .fold(template.to_string(), |message, (key, value)| {
+            message.replace(&format!("{{{}}}", key), value)
+        })
+}
+
+/// Downloads an image and scores it on the blocking pool, so that decoding does not stall the
+/// streams and the HTTP server running on the same worker.
+async fn calc_yakudo_score(pipeline: &Arc<Pipeline>, url: &Url) -> anyhow::Result<Vec<ImageScore>> {
     let image_bytes = reqwest::get(url.clone()).await?.bytes().await?.to_vec();
-    let image = opencv::imgcodecs::imdecode(
-        &opencv::core::Vector::<u8>::from_slice(&image_bytes),
-        opencv::imgcodecs::IMREAD_COLOR,
-    )?;
-    let mut result = opencv::core::Mat::default();
-    opencv::imgproc::laplacian(
-        &image,
-        &mut result,
-        opencv::core::CV_64F,
-        1,
-        1.0,
-        0.0,
-        opencv::core::BORDER_DEFAULT,
-    )
-    .context("failed to calculate yakudo score")?;
-
-    let sum = result
-        .iter::<opencv::core::Point3_<f64>>()
-        .unwrap()
-        .map(|(_, p)| p.x + p.y + p.z)
-        .sum::<f64>();
-    let mean = sum / (result.rows() * result.cols() * 3) as f64;
-    let variance = result
-        .iter::<opencv::core::Point3_<f64>>()
-        .unwrap()
-        .map(|(_, p)| (p.x - mean).powi(2) + (p.y - mean).powi(2) + (p.z - mean).powi(2))
-        .sum::<f64>()
-        / (result.rows() * result.cols() * 3) as f64;
-
-    let score = 1.0 / variance * 10000.0;
-
-    Ok(score)
+    let pipeline = pipeline.clone();
+    tokio::task::spawn_blocking(move || pipeline.score_frames_bytes(&image_bytes)).await?
+}
+
+/// Like `calc_yakudo_score`, for videos, whose decoding takes the longest.
+async fn calc_video_yakudo_score(
+    pipeline: &Arc<Pipeline>,
+    url: &Url,
+) -> anyhow::Result<VideoScore> {
+    let video_bytes = reqwest::get(url.clone()).await?.bytes().await?.to_vec();
+    let pipeline = pipeline.clone();
+    tokio::task::spawn_blocking(move || video::score_video_bytes(&pipeline, &video_bytes)).await?
 }
@@ -0,0 +1,439 @@
+use std::{collections::HashMap, future::Future, sync::Arc};
+
+use anyhow::Context;
+use chrono::{DateTime, Datelike, Duration, Local, NaiveDate, TimeZone};
+use misskey::{
+    model::{id::Id, note::Note},
+    ClientExt,
+};
+use sea_orm::prelude::*;
+
+use crate::{
+    config::{self, HashtagConfig},
+    database::get_db,
+    entity::yakudo_score,
+    metrics,
+    misskey::Misskey,
+    users,
+};
+
+#[derive(Clone, Copy, Debug)]
+pub enum Period {
+    Weekly,
+    Monthly,
+    Yearly,
+}
+impl Period {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Period::Weekly => "weekly",
+            Period::Monthly => "monthly",
+            Period::Yearly => "yearly",
+        }
+    }
+
+    /// The last complete period before `day` as a half-open range `[start, end)`. Weeks start on
+    /// Monday.
+    pub fn previous_range(&self, day: NaiveDate) -> (NaiveDate, NaiveDate) {
+        match self {
+            Period::Weekly => {
+                let end = day - Duration::days(day.weekday().num_days_from_monday() as i64);
+                (end - Duration::days(7), end)
+            }
+            Period::Monthly => {
+                let end = day.with_day(1).unwrap();
+                let start = (end - Duration::days(1)).with_day(1).unwrap();
+                (start, end)
+            }
+            Period::Yearly => {
+                let end = NaiveDate::from_ymd_opt(day.year(), 1, 1).unwrap();
+                let start = NaiveDate::from_ymd_opt(day.year() - 1, 1, 1).unwrap();
+                (start, end)
+            }
+        }
+    }
+
+    fn title<'a>(&self, messages: &'a config::Messages) -> &'a str {
+        match self {
+            Period::Weekly => &messages.weekly_report_title,
+            Period::Monthly => &messages.monthly_report_title,
+            Period::Yearly => &messages.yearly_report_title,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Summary {
+    pub posts: usize,
+    pub participants: usize,
+    /// The average of the valid (positive) scores.
+    pub average: Option<f64>,
+    /// The acct (`username` or `username@host`) of the user with the most posts and the number of
+    /// their posts.
+    pub most_active: Option<(String, usize)>,
+    /// The best post of each user, ranked by score. Users tied at the last place are all
+    /// included, so this may be longer than `top_n`.
+    pub ranking: Vec<RankedYakudo>,
+}
+
+#[derive(Debug)]
+pub struct RankedYakudo {
+    /// 1-based. Tied scores share the same place.
+    pub place: usize,
+    pub yakudo: yakudo_score::Model,
+}
+
+/// The start of `day` in local time, or the first hour after it where a DST change skips
+/// midnight. Bounds are bound with their time zone, as a naive time is taken for UTC (MySQL) or
+/// for the session time zone (PostgreSQL) rather than the bot's.
+pub fn start_of_day(day: NaiveDate) -> DateTime<Local> {
+    let midnight = day.and_hms_opt(0, 0, 0).unwrap();
+    Local
+        .from_local_datetime(&midnight)
+        .earliest()
+        .or_else(|| {
+            Local
+                .from_local_datetime(&(midnight + Duration::hours(1)))
+                .earliest()
+        })
+        .unwrap()
+}
+
+/// Returns the yakudos of `hashtag` posted in `[start, end)`, with the users of old records
+/// resolved by `users::resolve_legacy`.
+pub async fn yakudos_between(
+    hashtag: &str,
+    start: NaiveDate,
+    end: NaiveDate,
+) -> anyhow::Result<Vec<yakudo_score::Model>> {
+    let mut yakudos = yakudo_score::Entity::find()
+        .filter(yakudo_score::Column::Date.gte(start_of_day(start)))
+        .filter(yakudo_score::Column::Date.lt(start_of_day(end)))
+        .filter(yakudo_score::Column::Hashtag.eq(hashtag))
+        .all(get_db().await?)
+        .await
+        .context("failed to get yakudos")?;
+    users::resolve_legacy(&mut yakudos).await?;
+    Ok(yakudos)
+}
+
+/// Collects the statistics of the yakudos of `hashtag` posted in `[start, end)`.
+pub async fn summarize(
+    hashtag: &str,
+    start: NaiveDate,
+    end: NaiveDate,
+    top_n: usize,
+) -> anyhow::Result<Summary> {
+    let yakudos = yakudos_between(hashtag, start, end).await?;
+
+    let mut posts_per_user = HashMap::<String, (String, usize)>::new();
+    for yakudo in &yakudos {
+        posts_per_user
+            .entry(yakudo.user_key())
+            .or_insert_with(|| (yakudo.acct(), 0))
+            .1 += 1;
+    }
+    let participants = posts_per_user.len();
+    let most_active = posts_per_user
+        .into_values()
+        .max_by(|a, b| a.1.cmp(&b.1).then_with(|| b.0.cmp(&a.0)));
+
+    let valid = yakudos.iter().filter(|y| y.score > 0.0).collect::<Vec<_>>();
+    let average = if valid.is_empty() {
+        None
+    } else {
+        Some(valid.iter().map(|y| y.score).sum::<f64>() / valid.len() as f64)
+    };
+
+    Ok(Summary {
+        posts: yakudos.len(),
+        participants,
+        average,
+        most_active,
+        ranking: rank(valid.into_iter().cloned().collect(), top_n),
+    })
+}
+
+/// Ranks the best yakudo of each user and keeps the top `top_n` places.
+pub fn rank(yakudos: Vec<yakudo_score::Model>, top_n: usize) -> Vec<RankedYakudo> {
+    let mut best_per_user = HashMap::<String, yakudo_score::Model>::new();
+    for yakudo in yakudos {
+        match best_per_user.get(&yakudo.user_key()) {
+            Some(best) if best.score >= yakudo.score => {}
+            _ => {
+                best_per_user.insert(yakudo.user_key(), yakudo);
+            }
+        }
+    }
+
+    let mut best = best_per_user.into_values().collect::<Vec<_>>();
+    best.sort_by(|a, b| {
+        b.score
+            .total_cmp(&a.score)
+            .then_with(|| a.date.cmp(&b.date))
+    });
+
+    let mut ranking = Vec::<RankedYakudo>::new();
+    for (i, yakudo) in best.into_iter().enumerate() {
+        let place = match ranking.last() {
+            Some(last) if last.yakudo.score == yakudo.score => last.place,
+            _ => i + 1,
+        };
+        if place > top_n {
+            break;
+        }
+        ranking.push(RankedYakudo { place, yakudo });
+    }
+    ranking
+}
+
+/// Formats a ranking as one line per place with a link to each note. The users are mentioned
+/// only when `mention` is set.
+pub fn format_ranking(
+    misskey: &Misskey,
+    ranking: &[RankedYakudo],
+    mention: bool,
+) -> anyhow::Result<String> {
+    let mut message = String::new();
+    for ranked in ranking {
+        message.push_str(&format!(
+            "{}位 {}{} {:.3} {}\n",
+            ranked.place,
+            if mention { "@" } else { "" },
+            ranked.yakudo.acct(),
+            ranked.yakudo.score,
+            misskey.get_note_url(ranked.yakudo.note_id.parse::<Id<Note>>()?)
+        ));
+    }
+    Ok(message)
+}
+
+/// Announces the summary of the last complete `period` before `day` for each hashtag with a
+/// leaderboard.
+pub async fn periodic_report(
+    misskey: Arc<Misskey>,
+    period: Period,
+    day: NaiveDate,
+) -> anyhow::Result<()> {
+    let (start, end) = period.previous_range(day);
+    info!("{} report for {}..{} started", period.name(), start, end);
+
+    for_each_leaderboard(&format!("{} report", period.name()), |hashtag| {
+        periodic_report_for(&misskey, period, hashtag, start, end)
+    })
+    .await
+}
+
+async fn periodic_report_for(
+    misskey: &Misskey,
+    period: Period,
+    hashtag: &HashtagConfig,
+    start: NaiveDate,
+    end: NaiveDate,
+) -> anyhow::Result<()> {
+    let config = config::get();
+    let summary = summarize(&hashtag.name, start, end, config.report.top_n).await?;
+    info!("summary for #{}: {:?}", hashtag.name, summary);
+
+    let mut message = String::new();
+    if config.hashtags.len() > 1 {
+        message.push_str(&format!("#{}\n", hashtag.name));
+    }
+    message.push_str(period.title(&config.messages));
+    message.push('\n');
+    message.push_str(&format!("期間:{}〜{}\n", start, end - Duration::days(1)));
+
+    if summary.posts == 0 {
+        message.push_str(&config.messages.periodic_no_yakudo);
+    } else {
+        message.push_str(&format!(
+            "参加者:{}人 投稿:{}件\n",
+            summary.participants, summary.posts
+        ));
+        if let Some(average) = summary.average {
+            message.push_str(&format!("平均スコア:{:.3}\n", average));
+        }
+        if let Some((acct, posts)) = &summary.most_active {
+            message.push_str(&format!("最多投稿:{} ({}件)\n", acct, posts));
+        }
+        message.push_str(&format_ranking(
+            misskey,
+            &summary.ranking,
+            config.report.mention,
+        )?);
+        if !summary.ranking.is_empty() {
+            message.push_str(&config.messages.daily_winner);
+        }
+    }
+
+    metrics::api_result("create_note", misskey.create_note(&message).await)?;
+    info!("message: {}", message);
+
+    Ok(())
+}
+
+/// Runs `report` for each hashtag with a leaderboard. A failure is logged and the other hashtags
+/// still get their report, but the whole fails at the end so that the job is counted as failed.
+pub async fn for_each_leaderboard<F, Fut>(name: &str, mut report: F) -> anyhow::Result<()>
+where
+    F: FnMut(&'static HashtagConfig) -> Fut,
+    Fut: Future<Output = anyhow::Result<()>>,
+{
+    let mut failed = vec![];
+    for hashtag in config::get().hashtags.iter().filter(|h| h.leaderboard) {
+        if let Err(err) = report(hashtag).await {
+            error!("{} for #{} failed: {:#}", name, hashtag.name, err);
+            failed.push(format!("#{}", hashtag.name));
+        }
+    }
+    if !failed.is_empty() {
+        return Err(anyhow::anyhow!("{} failed for {}", name, failed.join(", ")));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn yakudo(id: i32, user_id: Option<&str>, username: &str, score: f64) -> yakudo_score::Model {
+        yakudo_score::Model {
+            id,
+            username: username.to_string(),
+            user_id: user_id.map(str::to_string),
+            user_host: None,
+            note_id: format!("note{}", id),
+            quote_id: format!("quote{}", id),
+            score,
+            // later ids are posted later
+            date: Local
+                .timestamp_opt(1_700_000_000 + id as i64 * 60, 0)
+                .unwrap(),
+            verdict: None,
+            normalize_long_edge: None,
+            normalize_denoise_sigma: None,
+            hashtag: Some("mis1yakudo".to_string()),
+            aggregate: None,
+            scorer_version: None,
+            created_at: None,
+        }
+    }
+
+    fn places(ranking: &[RankedYakudo]) -> Vec<(usize, i32)> {
+        ranking.iter().map(|r| (r.place, r.yakudo.id)).collect()
+    }
+
+    #[test]
+    fn best_per_user() {
+        let ranking = rank(
+            vec![
+                yakudo(1, Some("a"), "alice", 3.0),
+                yakudo(2, Some("a"), "alice", 5.0),
+                yakudo(3, Some("b"), "bob", 4.0),
+            ],
+            usize::MAX,
+        );
+        assert_eq!(places(&ranking), [(1, 2), (2, 3)]);
+    }
+
+    #[test]
+    fn users_by_id() {
+        // the same username on two instances, and old records of the local one
+        let mut yakudos = vec![
+            yakudo(1, Some("a1"), "alice", 3.0),
+            yakudo(2, Some("a2"), "alice", 2.0),
+            yakudo(3, None, "alice", 4.0),
+            yakudo(4, None, "alice", 0.5),
+            // no local user by that name is known
+            yakudo(5, None, "bob", 1.0),
+        ];
+        let ids = HashMap::from([("alice".to_string(), "a1".to_string())]);
+        users::assign_local_ids(&mut yakudos, &ids);
+
+        let ranking = rank(yakudos, usize::MAX);
+        assert_eq!(places(&ranking), [(1, 3), (2, 2), (3, 5)]);
+    }
+
+    #[test]
+    fn ties_share_places() {
+        let ranking = rank(
+            vec![
+                yakudo(1, Some("a"), "alice", 3.0),
+                yakudo(2, Some("b"), "bob", 5.0),
+                yakudo(3, Some("c"), "carol", 5.0),
+                yakudo(4, Some("d"), "dave", 4.0),
+            ],
+            usize::MAX,
+        );
+        // the earlier of the tied posts first, and the place after the tie skipped
+        assert_eq!(places(&ranking), [(1, 2), (1, 3), (3, 4), (4, 1)]);
+    }
+
+    #[test]
+    fn ties_at_the_last_place_are_kept() {
+        let yakudos = vec![
+            yakudo(1, Some("a"), "alice", 5.0),
+            yakudo(2, Some("b"), "bob", 4.0),
+            yakudo(3, Some("c"), "carol", 4.0),
+            yakudo(4, Some("d"), "dave", 3.0),
+        ];
+        assert_eq!(places(&rank(yakudos.clone(), 2)), [(1, 1), (2, 2), (2, 3)]);
+        assert_eq!(places(&rank(yakudos.clone(), 1)), [(1, 1)]);
+        assert!(rank(yakudos, 0).is_empty());
+    }
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    #[test]
+    fn weeks_start_on_monday() {
+        // a Sunday is still in the week of the Monday before it
+        assert_eq!(
+            Period::Weekly.previous_range(date(2026, 10, 18)),
+            (date(2026, 10, 5), date(2026, 10, 12))
+        );
+        assert_eq!(
+            Period::Weekly.previous_range(date(2026, 10, 19)),
+            (date(2026, 10, 12), date(2026, 10, 19))
+        );
+        // across the new year
+        assert_eq!(
+            Period::Weekly.previous_range(date(2026, 1, 5)),
+            (date(2025, 12, 29), date(2026, 1, 5))
+        );
+    }
+
+    #[test]
+    fn months() {
+        assert_eq!(
+            Period::Monthly.previous_range(date(2026, 10, 18)),
+            (date(2026, 9, 1), date(2026, 10, 1))
+        );
+        assert_eq!(
+            Period::Monthly.previous_range(date(2026, 3, 1)),
+            (date(2026, 2, 1), date(2026, 3, 1))
+        );
+        // January goes back to the December of the year before
+        assert_eq!(
+            Period::Monthly.previous_range(date(2026, 1, 1)),
+            (date(2025, 12, 1), date(2026, 1, 1))
+        );
+        assert_eq!(
+            Period::Monthly.previous_range(date(2026, 1, 31)),
+            (date(2025, 12, 1), date(2026, 1, 1))
+        );
+    }
+
+    #[test]
+    fn years() {
+        assert_eq!(
+            Period::Yearly.previous_range(date(2027, 1, 1)),
+            (date(2026, 1, 1), date(2027, 1, 1))
+        );
+        assert_eq!(
+            Period::Yearly.previous_range(date(2026, 12, 31)),
+            (date(2025, 1, 1), date(2026, 1, 1))
+        );
+    }
+}
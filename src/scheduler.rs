@@ -1,32 +1,178 @@
 use anyhow::Context;
-use chrono::Timelike;
+use chrono::{DateTime, Local, NaiveDate, Utc};
 use misskey::{
     model::{id::Id, note::Note},
     ClientExt,
 };
-use sea_orm::{prelude::*, QueryOrder};
-use std::{future::Future, ops::Add, pin::Pin, sync::Arc, time::Duration};
+use sea_orm::{prelude::*, ActiveValue};
+use serde::Deserialize;
+use std::{future::Future, pin::Pin, str::FromStr, sync::Arc, time::Duration};
 use tokio::time::sleep;
 
-use crate::{database::get_db, entity::yakudo_score, follow::follow_followers, misskey::Misskey};
+use crate::{
+    config,
+    database::get_db,
+    entity::{job_run, yakudo_score},
+    follow::follow_followers,
+    metrics,
+    misskey::Misskey,
+    monitor,
+    report::{self, periodic_report, Period},
+    users,
+};
+
+/// How long catch-up runs wait for the missed notes to be backfilled.
+const BACKFILL_WAIT: Duration = Duration::from_secs(300);
+/// The number of latest missed runs of a job made up for. Older ones are skipped.
+const MAX_CATCH_UP_RUNS: usize = 7;
+
+/// A cron expression. Both the standard five-field form (`59 23 * * *`, weekdays 0-7 with 0 and
+/// 7 for Sunday) and the six- or seven-field form of the `cron` crate with seconds (and years,
+/// weekdays 1-7 with 1 for Sunday) are accepted. Weekday names (`Mon`) work in both.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(try_from = "String")]
+pub struct CronSchedule(cron::Schedule);
+impl CronSchedule {
+    /// Returns the first occurrence strictly after `time`.
+    pub fn next_after(&self, time: &DateTime<Local>) -> Option<DateTime<Local>> {
+        self.0.after(time).next()
+    }
+}
+impl TryFrom<String> for CronSchedule {
+    type Error = anyhow::Error;
+
+    fn try_from(expression: String) -> Result<Self, Self::Error> {
+        let fields = expression.split_whitespace().collect::<Vec<_>>();
+        let expression = if let [minute, hour, day, month, weekday] = fields[..] {
+            format!(
+                "0 {} {} {} {} {}",
+                minute,
+                hour,
+                day,
+                month,
+                weekday_names(weekday)?
+            )
+        } else {
+            expression
+        };
+        let schedule = cron::Schedule::from_str(&expression)
+            .with_context(|| format!("invalid cron expression: {}", expression))?;
+        Ok(CronSchedule(schedule))
+    }
+}
+
+/// Replaces the standard weekday numbers (0-7, Sunday first and last) in a day-of-week field with
+/// names, as the `cron` crate numbers them from 1 for Sunday. Ranges ending with 7 are listed day
+/// by day, as `Fri-Sun` would run backwards through the week.
+fn weekday_names(field: &str) -> anyhow::Result<String> {
+    const NAMES: [&str; 7] = ["Sun", "Mon", "Tue", "Wed", "Thu", "Fri", "Sat"];
+    let number = |n: &str| -> anyhow::Result<Option<usize>> {
+        match n.parse::<usize>() {
+            Ok(n) if n <= 7 => Ok(Some(n)),
+            Ok(n) => Err(anyhow::anyhow!("invalid weekday: {}", n)),
+            Err(_) => Ok(None),
+        }
+    };
+    let name = |n: &str| -> anyhow::Result<String> {
+        Ok(match number(n)? {
+            Some(n) => NAMES[n % 7].to_string(),
+            None => n.to_string(),
+        })
+    };
+    field
+        .split(',')
+        .map(|item| {
+            // only the range is a weekday. the step after `/` is a count
+            let (range, step) = match item.split_once('/') {
+                Some((range, step)) => (range, Some(step)),
+                None => (item, None),
+            };
+            let mut item = match range.split_once('-') {
+                Some((from, to)) => match (number(from)?, number(to)?) {
+                    (Some(from), Some(7)) => {
+                        let step = match step {
+                            Some(step) => step
+                                .parse::<usize>()
+                                .ok()
+                                .filter(|&step| step > 0)
+                                .ok_or_else(|| anyhow::anyhow!("invalid step: {}", step))?,
+                            None => 1,
+                        };
+                        let mut days = (from..=7).step_by(step).map(|n| n % 7).collect::<Vec<_>>();
+                        days.sort_unstable();
+                        days.dedup();
+                        return Ok(days
+                            .into_iter()
+                            .map(|n| NAMES[n])
+                            .collect::<Vec<_>>()
+                            .join(","));
+                    }
+                    _ => format!("{}-{}", name(from)?, name(to)?),
+                },
+                None => name(range)?,
+            };
+            if let Some(step) = step {
+                item.push('/');
+                item.push_str(step);
+            }
+            Ok(item)
+        })
+        .collect::<anyhow::Result<Vec<_>>>()
+        .map(|items| items.join(","))
+}
+
+type JobFuture = Pin<Box<dyn Future<Output = anyhow::Result<()>> + Send + 'static>>;
+type JobFn = dyn FnMut(DateTime<Local>) -> JobFuture + Send + Sync;
 
 pub struct Job {
-    hour: Option<u32>,
-    minute: Option<u32>,
-    job: Box<dyn FnMut() -> Pin<Box<dyn Future<Output = ()> + Send + 'static>> + Send + Sync>,
+    /// The key of the last run timestamp in the database.
+    name: &'static str,
+    schedule: CronSchedule,
+    /// Called with the time the run was scheduled for, which is in the past for catch-up runs.
+    job: Box<JobFn>,
 }
 impl Job {
     pub fn new(
-        hour: impl Into<Option<u32>>,
-        minute: impl Into<Option<u32>>,
-        job: impl FnMut() -> Pin<Box<dyn Future<Output = ()> + Send + 'static>> + Send + Sync + 'static,
+        name: &'static str,
+        schedule: CronSchedule,
+        job: impl FnMut(DateTime<Local>) -> JobFuture + Send + Sync + 'static,
     ) -> Self {
         Self {
-            hour: hour.into(),
-            minute: minute.into(),
+            name,
+            schedule,
             job: Box::new(job),
         }
     }
+
+    /// Runs the job once for each of `scheduled` in order, one after another.
+    fn run(&mut self, scheduled: Vec<DateTime<Local>>) {
+        let name = self.name;
+        let runs = scheduled
+            .into_iter()
+            .map(|scheduled| (scheduled, (self.job)(scheduled)))
+            .collect::<Vec<_>>();
+        tokio::spawn(async move {
+            for (scheduled, future) in runs {
+                info!("running job {} scheduled at {}", name, scheduled);
+                let outcome = match future.await {
+                    Ok(()) => "success",
+                    Err(err) => {
+                        error!("job {} failed: {:#}", name, err);
+                        "failure"
+                    }
+                };
+                metrics::get()
+                    .jobs
+                    .with_label_values(&[name, outcome])
+                    .inc();
+                // the scheduled time rather than now, so that the runs still missed are made up
+                // for if the bot stops while catching up
+                if let Err(err) = save_last_run(name, scheduled.with_timezone(&Utc)).await {
+                    error!("failed to save last run of job {}: {:#}", name, err);
+                }
+            }
+        });
+    }
 }
 pub struct Scheduler {
     jobs: Vec<Job>,
@@ -38,117 +184,235 @@ impl Scheduler {
     pub fn add(&mut self, job: Job) {
         self.jobs.push(job);
     }
+    /// Starts running the jobs. The runs of a job missed since its last recorded run (because the
+    /// bot was down at the time) are made up for right away, up to the latest
+    /// `MAX_CATCH_UP_RUNS` of them.
     pub fn start(self) {
         tokio::spawn(async move {
             let mut jobs = self.jobs;
 
+            let mut next_runs = vec![];
+            for job in &jobs {
+                let now = Local::now();
+                let next_run = match load_last_run(job.name).await {
+                    Ok(Some(last_run)) => job.schedule.next_after(&last_run.with_timezone(&Local)),
+                    Ok(None) => {
+                        // first start with this job. there is nothing to catch up on
+                        if let Err(err) = save_last_run(job.name, now.with_timezone(&Utc)).await {
+                            error!("failed to save last run of job {}: {:#}", job.name, err);
+                        }
+                        job.schedule.next_after(&now)
+                    }
+                    Err(err) => {
+                        error!("failed to load last run of job {}: {:#}", job.name, err);
+                        job.schedule.next_after(&now)
+                    }
+                };
+                info!("next run of job {}: {:?}", job.name, next_run);
+                next_runs.push(next_run);
+            }
+
+            if next_runs.iter().flatten().any(|t| *t <= Local::now()) {
+                info!("waiting for backfill before catching up on missed runs...");
+                monitor::wait_for_backfill(BACKFILL_WAIT).await;
+            }
+
             loop {
-                let now = chrono::Local::now();
-                sleep(Duration::from_millis(
-                    (now.add(chrono::Duration::minutes(1))
-                        .with_second(0)
-                        .unwrap()
-                        - now)
-                        .num_milliseconds() as u64,
-                ))
-                .await;
-
-                let now = chrono::Local::now();
-                for job in &mut jobs {
-                    if job.hour.map(|hour| hour == now.hour()).unwrap_or(true)
-                        && job
-                            .minute
-                            .map(|minute| minute == now.minute())
-                            .unwrap_or(true)
-                    {
-                        tokio::spawn((job.job)());
+                let now = Local::now();
+                for (job, next_run) in jobs.iter_mut().zip(&mut next_runs) {
+                    let mut due = vec![];
+                    while let Some(scheduled) = next_run.filter(|t| *t <= now) {
+                        due.push(scheduled);
+                        *next_run = job.schedule.next_after(&scheduled);
+                    }
+                    if due.is_empty() {
+                        continue;
+                    }
+                    if due.len() > MAX_CATCH_UP_RUNS {
+                        warn!(
+                            "skipping {} missed run(s) of job {}",
+                            due.len() - MAX_CATCH_UP_RUNS,
+                            job.name
+                        );
+                        due.drain(..due.len() - MAX_CATCH_UP_RUNS);
                     }
+                    if now - due[0] > chrono::Duration::minutes(1) {
+                        info!("catching up on {} run(s) of job {}", due.len(), job.name);
+                    }
+                    job.run(due);
                 }
+
+                // wake up at least once a minute in case the system clock jumps
+                let wait = next_runs
+                    .iter()
+                    .flatten()
+                    .min()
+                    .map(|t| (*t - Local::now()).to_std().unwrap_or_default())
+                    .unwrap_or(Duration::from_secs(60))
+                    .min(Duration::from_secs(60));
+                sleep(wait).await;
             }
         });
     }
 }
 
+async fn load_last_run(name: &str) -> anyhow::Result<Option<DateTime<Utc>>> {
+    Ok(job_run::Entity::find_by_id(name.to_string())
+        .one(get_db().await?)
+        .await?
+        .map(|job_run| job_run.last_run))
+}
+
+async fn save_last_run(name: &str, time: DateTime<Utc>) -> anyhow::Result<()> {
+    let db = get_db().await?;
+    match job_run::Entity::find_by_id(name.to_string())
+        .one(db)
+        .await?
+    {
+        Some(job_run) => {
+            let mut job_run: job_run::ActiveModel = job_run.into();
+            job_run.last_run = ActiveValue::Set(time);
+            job_run.update(db).await?;
+        }
+        None => {
+            job_run::ActiveModel {
+                name: ActiveValue::Set(name.to_string()),
+                last_run: ActiveValue::Set(time),
+            }
+            .insert(db)
+            .await?;
+        }
+    }
+    Ok(())
+}
+
 pub async fn start_scheduler(misskey: Arc<Misskey>) -> anyhow::Result<()> {
+    let schedule = &config::get().schedule;
     let mut sched = Scheduler::new();
 
     let misskey_clone = misskey.clone();
-    sched.add(Job::new(23, 59, move || {
-        let misskey = misskey_clone.clone();
-        Box::pin(async move {
-            log_error(daily_report(misskey)).await;
-        })
-    }));
+    sched.add(Job::new(
+        "daily_report",
+        schedule.daily_report.clone(),
+        move |scheduled| {
+            let misskey = misskey_clone.clone();
+            Box::pin(daily_report(misskey, scheduled.date_naive()))
+        },
+    ));
 
     let misskey_clone = misskey.clone();
-    sched.add(Job::new(None, 50, move || {
-        let misskey = misskey_clone.clone();
-        Box::pin(async move {
-            log_error(destroy_deleted_notes(misskey)).await;
-        })
-    }));
+    sched.add(Job::new(
+        "destroy_deleted_notes",
+        schedule.destroy_deleted_notes.clone(),
+        move |scheduled| {
+            let misskey = misskey_clone.clone();
+            Box::pin(destroy_deleted_notes(misskey, scheduled.date_naive()))
+        },
+    ));
+
+    for (name, period, cron) in [
+        ("weekly_report", Period::Weekly, &schedule.weekly_report),
+        ("monthly_report", Period::Monthly, &schedule.monthly_report),
+        ("yearly_report", Period::Yearly, &schedule.yearly_report),
+    ] {
+        let misskey_clone = misskey.clone();
+        sched.add(Job::new(name, cron.clone(), move |scheduled| {
+            let misskey = misskey_clone.clone();
+            Box::pin(periodic_report(misskey, period, scheduled.date_naive()))
+        }));
+    }
+
+    let misskey_clone = misskey.clone();
+    sched.add(Job::new(
+        "refresh_users",
+        schedule.refresh_users.clone(),
+        move |_| {
+            let misskey = misskey_clone.clone();
+            Box::pin(users::refresh_users(misskey))
+        },
+    ));
 
     let misskey_clone = misskey;
-    sched.add(Job::new(0, 0, move || {
-        let misskey = misskey_clone.clone();
-        Box::pin(async move {
-            log_error(follow_followers(misskey)).await;
-        })
-    }));
+    sched.add(Job::new(
+        "follow_followers",
+        schedule.follow_followers.clone(),
+        move |_| {
+            let misskey = misskey_clone.clone();
+            Box::pin(follow_followers(misskey))
+        },
+    ));
 
     sched.start();
 
     Ok(())
 }
 
-async fn log_error(future: impl std::future::Future<Output = anyhow::Result<()>>) {
-    if let Err(err) = future.await {
-        error!("{:#}", err);
-    }
+/// Announces the best yakudo of `day`. `day` is the day the report was scheduled for, so a
+/// report caught up on after downtime still covers the right day.
+async fn daily_report(misskey: Arc<Misskey>, day: NaiveDate) -> anyhow::Result<()> {
+    info!("daily report for {} started", day);
+
+    let config = config::get();
+    report::for_each_leaderboard("daily report", |hashtag| {
+        let misskey = misskey.clone();
+        async move {
+            // name the hashtag only when there is more than one leaderboard
+            let header = if config.hashtags.len() > 1 {
+                format!("#{}\n", hashtag.name)
+            } else {
+                String::new()
+            };
+            daily_report_for(&misskey, day, &hashtag.name, &header).await
+        }
+    })
+    .await
 }
 
-async fn daily_report(misskey: Arc<Misskey>) -> anyhow::Result<()> {
-    info!("daily report started");
+async fn daily_report_for(
+    misskey: &Misskey,
+    day: NaiveDate,
+    hashtag: &str,
+    header: &str,
+) -> anyhow::Result<()> {
+    let config = config::get();
+    let messages = &config.messages;
 
-    let yakudos = yakudo_score::Entity::find()
-        .filter(
-            yakudo_score::Column::Date.gt(chrono::Local::now().date_naive().and_hms_opt(0, 0, 0)),
-        )
-        .order_by_desc(yakudo_score::Column::Score)
-        .all(get_db().await?)
-        .await
-        .context("failed to get yakudos")?;
+    let yakudos = report::yakudos_between(hashtag, day, day.succ_opt().unwrap()).await?;
 
-    info!("yakudos: {:?}", yakudos);
+    info!("yakudos for #{}: {:?}", hashtag, yakudos);
 
-    if let Some(best_yakudo) = yakudos.first() {
-        if best_yakudo.score > 0.0 {
-            let message = format!("Highest Score:{:.3}\n優勝おめでとう!", best_yakudo.score);
-            misskey
-                .quote(best_yakudo.note_id.parse::<Id<Note>>()?, &message)
-                .await?;
-            info!("message: {}", message);
-        } else {
-            let message = "おい待てや...今日のyakudo...-inf点しか無いやん...";
-            misskey.create_note(message).await?;
-            info!("message: {}", message);
-        }
+    let valid = yakudos.iter().filter(|y| y.score > 0.0).cloned().collect();
+    let ranking = report::rank(valid, config.report.daily_top_n);
+
+    if let Some(best_yakudo) = ranking.first() {
+        // quote the (earliest) first place and list the rest of the ranking
+        let message = format!(
+            "{}{}{}",
+            header,
+            report::format_ranking(misskey, &ranking, config.report.mention)?,
+            messages.daily_winner
+        );
+        let note_id = best_yakudo.yakudo.note_id.parse::<Id<Note>>()?;
+        metrics::api_result("quote", misskey.quote(note_id, &message).await)?;
+        info!("message: {}", message);
+    } else if !yakudos.is_empty() {
+        let message = format!("{}{}", header, messages.daily_no_score);
+        metrics::api_result("create_note", misskey.create_note(&message).await)?;
+        info!("message: {}", message);
     } else {
-        let message = "本日のyakudoは...何一つ...出ませんでした...";
-        misskey.create_note(message).await?;
+        let message = format!("{}{}", header, messages.daily_no_yakudo);
+        metrics::api_result("create_note", misskey.create_note(&message).await)?;
         info!("message: {}", message);
     }
 
     Ok(())
 }
 
-async fn destroy_deleted_notes(misskey: Arc<Misskey>) -> anyhow::Result<()> {
+async fn destroy_deleted_notes(misskey: Arc<Misskey>, day: NaiveDate) -> anyhow::Result<()> {
     info!("destroy deleted notes started");
 
     let yakudos = yakudo_score::Entity::find()
-        .filter(
-            yakudo_score::Column::Date.gt(chrono::Local::now().date_naive().and_hms_opt(0, 0, 0)),
-        )
+        .filter(yakudo_score::Column::Date.gte(report::start_of_day(day)))
         .all(get_db().await?)
         .await
         .context("failed to get yakudos")?;
@@ -159,15 +423,13 @@ async fn destroy_deleted_notes(misskey: Arc<Misskey>) -> anyhow::Result<()> {
         info!("checking note: {}", yakudo.note_id);
 
         let note_id = yakudo.note_id.parse::<Id<Note>>()?;
-        if misskey.get_note(note_id).await.is_err() {
+        if metrics::api_result("get_note", misskey.get_note(note_id).await).is_err() {
             info!(
                 "failed to get note {}. deleting quote and database record...",
                 yakudo.note_id
             );
 
-            misskey
-                .delete_note(note_id)
-                .await
+            metrics::api_result("delete_note", misskey.delete_note(note_id).await)
                 .context("failed to delete note")?;
             yakudo_score::Entity::delete_by_id(yakudo.id)
                 .exec(get_db().await?)
@@ -181,3 +443,54 @@ async fn destroy_deleted_notes(misskey: Arc<Misskey>) -> anyhow::Result<()> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Datelike, TimeZone, Weekday};
+
+    use super::*;
+
+    fn weekdays(expression: &str) -> Vec<Weekday> {
+        let schedule = CronSchedule::try_from(expression.to_string()).unwrap();
+        // a Monday
+        let mut time = Local.with_ymd_and_hms(2026, 10, 12, 12, 0, 0).unwrap();
+        let mut weekdays = vec![];
+        for _ in 0..7 {
+            time = schedule.next_after(&time).unwrap();
+            weekdays.push(time.weekday());
+        }
+        weekdays
+    }
+
+    #[test]
+    fn weekday_numbers() {
+        assert_eq!(weekday_names("0").unwrap(), "Sun");
+        assert_eq!(weekday_names("7").unwrap(), "Sun");
+        assert_eq!(weekday_names("1-5").unwrap(), "Mon-Fri");
+        assert_eq!(weekday_names("1-5/2").unwrap(), "Mon-Fri/2");
+        assert_eq!(weekday_names("0,3,Sat").unwrap(), "Sun,Wed,Sat");
+        assert_eq!(weekday_names("*").unwrap(), "*");
+        assert!(weekday_names("8").is_err());
+    }
+
+    #[test]
+    fn ranges_ending_with_sunday() {
+        assert_eq!(weekday_names("5-7").unwrap(), "Sun,Fri,Sat");
+        assert_eq!(weekday_names("0-7").unwrap(), "Sun,Mon,Tue,Wed,Thu,Fri,Sat");
+        assert_eq!(weekday_names("1-7").unwrap(), "Sun,Mon,Tue,Wed,Thu,Fri,Sat");
+        assert_eq!(weekday_names("1-7/2").unwrap(), "Sun,Mon,Wed,Fri");
+        assert_eq!(weekday_names("7-7").unwrap(), "Sun");
+        assert!(weekday_names("1-7/0").is_err());
+    }
+
+    #[test]
+    fn five_fields() {
+        use Weekday::*;
+        assert_eq!(weekdays("0 9 * * 5-7"), [Fri, Sat, Sun, Fri, Sat, Sun, Fri]);
+        assert_eq!(weekdays("0 9 * * 0-7"), [Tue, Wed, Thu, Fri, Sat, Sun, Mon]);
+        assert_eq!(weekdays("0 9 * * 1-5"), [Tue, Wed, Thu, Fri, Mon, Tue, Wed]);
+        assert_eq!(weekdays("0 9 * * 7"), [Sun; 7]);
+        // the cron crate's own form is passed through
+        assert_eq!(weekdays("0 0 9 * * 1"), [Sun; 7]);
+    }
+}
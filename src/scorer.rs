@@ -0,0 +1,480 @@
+use anyhow::Context;
+use opencv::{
+    core::{Mat, Point3_, Vec2d},
+    prelude::*,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    aggregate::Aggregate,
+    animation::{self, AnimationSampling},
+    config::ScoringConfig,
+    video::VideoSampling,
+};
+
+/// A metric that turns a decoded image into a yakudo score. Higher is more yakudo.
+pub trait YakudoScorer: Send + Sync {
+    /// The name used to select this scorer in the config.
+    fn name(&self) -> &'static str;
+
+    /// Bumped whenever the scores of this scorer change, so that records scored before can be
+    /// told apart.
+    fn version(&self) -> u32 {
+        1
+    }
+
+    fn score(&self, image: &Mat) -> anyhow::Result<f64>;
+}
+
+pub fn from_name(name: &str) -> anyhow::Result<Box<dyn YakudoScorer>> {
+    match name {
+        "laplacian" => Ok(Box::new(Laplacian)),
+        "tenengrad" => Ok(Box::new(Tenengrad)),
+        "fft" => Ok(Box::new(FftHighFrequency)),
+        _ => Err(anyhow::anyhow!(
+            "unknown scorer: {} (available: laplacian, tenengrad, fft)",
+            name
+        )),
+    }
+}
+
+#[derive(Clone, Copy, Debug, Serialize)]
+pub struct ImageScore {
+    /// The final score, including the motion blur bonus.
+    pub score: f64,
+    /// The score of the selected scorer alone.
+    pub base_score: f64,
+    pub motion_blur: MotionBlur,
+    /// The size of the image before normalization.
+    pub width: i32,
+    pub height: i32,
+}
+
+/// The selected scorer together with the preprocessing applied before it. Shared by the bot and
+/// the `score` subcommand so both produce the same numbers.
+pub struct Pipeline {
+    pub scorer: Box<dyn YakudoScorer>,
+    pub normalization: Normalization,
+    pub video: VideoSampling,
+    pub animation: AnimationSampling,
+    /// How the scores of the frames of an animation or a video are combined.
+    pub frame_aggregate: Aggregate,
+}
+impl Pipeline {
+    pub fn from_config(config: &ScoringConfig) -> anyhow::Result<Self> {
+        Ok(Pipeline {
+            scorer: from_name(&config.scorer)?,
+            normalization: config.normalization,
+            video: config.video,
+            animation: config.animation,
+            frame_aggregate: config.frame_aggregate,
+        })
+    }
+
+    pub fn score_image_bytes(&self, bytes: &[u8]) -> anyhow::Result<ImageScore> {
+        let image = decode_image(bytes)?;
+        self.score_image(&image)
+    }
+
+    /// Like `score_image_bytes`, but scores the sampled frames of animated images. Returns a
+    /// single score for still images.
+    pub fn score_frames_bytes(&self, bytes: &[u8]) -> anyhow::Result<Vec<ImageScore>> {
+        match animation::decode_frames(bytes, self.animation)? {
+            Some(frames) => frames.iter().map(|frame| self.score_image(frame)).collect(),
+            None => Ok(vec![self.score_image_bytes(bytes)?]),
+        }
+    }
+
+    pub fn score_image(&self, image: &Mat) -> anyhow::Result<ImageScore> {
+        let (width, height) = (image.cols(), image.rows());
+        let image = self.normalization.apply(image)?;
+        let base_score = self.scorer.score(&image)?;
+        let motion_blur = MotionBlur::estimate(&image)?;
+        Ok(ImageScore {
+            score: base_score * motion_blur.multiplier(),
+            base_score,
+            motion_blur,
+            width,
+            height,
+        })
+    }
+
+    /// Identifies the scorer and its version, e.g. `laplacian:1`.
+    pub fn scorer_version(&self) -> String {
+        format!("{}:{}", self.scorer.name(), self.scorer.version())
+    }
+}
+
+/// Preprocessing that makes scores independent of the resolution and recompression of the
+/// instance that served the image. Both steps are disabled by default so that scores stay
+/// comparable with ones recorded before normalization existed.
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Normalization {
+    /// Images are resized so that their longer edge has this many pixels.
+    pub long_edge: Option<i32>,
+    /// Sigma of the Gaussian blur applied to suppress compression noise.
+    pub denoise_sigma: Option<f64>,
+}
+impl Normalization {
+    pub fn apply(&self, image: &Mat) -> anyhow::Result<Mat> {
+        let mut image = image.clone();
+
+        if let Some(long_edge) = self.long_edge {
+            let (rows, cols) = (image.rows(), image.cols());
+            let scale = long_edge as f64 / rows.max(cols) as f64;
+            if scale != 1.0 {
+                let size = opencv::core::Size::new(
+                    ((cols as f64 * scale).round() as i32).max(1),
+                    ((rows as f64 * scale).round() as i32).max(1),
+                );
+                let interpolation = if scale < 1.0 {
+                    opencv::imgproc::INTER_AREA
+                } else {
+                    opencv::imgproc::INTER_LINEAR
+                };
+                let mut resized = Mat::default();
+                opencv::imgproc::resize(&image, &mut resized, size, 0.0, 0.0, interpolation)
+                    .context("failed to resize image")?;
+                image = resized;
+            }
+        }
+
+        if let Some(sigma) = self.denoise_sigma {
+            let mut denoised = Mat::default();
+            opencv::imgproc::gaussian_blur(
+                &image,
+                &mut denoised,
+                opencv::core::Size::new(0, 0),
+                sigma,
+                sigma,
+                opencv::core::BORDER_DEFAULT,
+            )
+            .context("failed to denoise image")?;
+            image = denoised;
+        }
+
+        Ok(image)
+    }
+}
+
+pub fn decode_image(bytes: &[u8]) -> anyhow::Result<Mat> {
+    let image = opencv::imgcodecs::imdecode(
+        &opencv::core::Vector::<u8>::from_slice(bytes),
+        opencv::imgcodecs::IMREAD_COLOR,
+    )?;
+    if image.empty() {
+        return Err(anyhow::anyhow!("failed to decode image"));
+    }
+    Ok(image)
+}
+
+/// Inverse of the variance of the Laplacian over all color channels.
+pub struct Laplacian;
+impl YakudoScorer for Laplacian {
+    fn name(&self) -> &'static str {
+        "laplacian"
+    }
+
+    fn score(&self, image: &Mat) -> anyhow::Result<f64> {
+        let mut result = Mat::default();
+        opencv::imgproc::laplacian(
+            image,
+            &mut result,
+            opencv::core::CV_64F,
+            1,
+            1.0,
+            0.0,
+            opencv::core::BORDER_DEFAULT,
+        )
+        .context("failed to calculate yakudo score")?;
+
+        let sum = result
+            .iter::<Point3_<f64>>()?
+            .map(|(_, p)| p.x + p.y + p.z)
+            .sum::<f64>();
+        let mean = sum / (result.rows() * result.cols() * 3) as f64;
+        let variance = result
+            .iter::<Point3_<f64>>()?
+            .map(|(_, p)| (p.x - mean).powi(2) + (p.y - mean).powi(2) + (p.z - mean).powi(2))
+            .sum::<f64>()
+            / (result.rows() * result.cols() * 3) as f64;
+
+        Ok(1.0 / variance * 10000.0)
+    }
+}
+
+/// Inverse of the mean Sobel gradient energy (Tenengrad) over all color channels.
+pub struct Tenengrad;
+impl YakudoScorer for Tenengrad {
+    fn name(&self) -> &'static str {
+        "tenengrad"
+    }
+
+    fn score(&self, image: &Mat) -> anyhow::Result<f64> {
+        let mut gx = Mat::default();
+        let mut gy = Mat::default();
+        opencv::imgproc::sobel(
+            image,
+            &mut gx,
+            opencv::core::CV_64F,
+            1,
+            0,
+            3,
+            1.0,
+            0.0,
+            opencv::core::BORDER_DEFAULT,
+        )
+        .context("failed to calculate horizontal gradient")?;
+        opencv::imgproc::sobel(
+            image,
+            &mut gy,
+            opencv::core::CV_64F,
+            0,
+            1,
+            3,
+            1.0,
+            0.0,
+            opencv::core::BORDER_DEFAULT,
+        )
+        .context("failed to calculate vertical gradient")?;
+
+        let energy = gx
+            .iter::<Point3_<f64>>()?
+            .zip(gy.iter::<Point3_<f64>>()?)
+            .map(|((_, x), (_, y))| {
+                x.x.powi(2) + x.y.powi(2) + x.z.powi(2) + y.x.powi(2) + y.y.powi(2) + y.z.powi(2)
+            })
+            .sum::<f64>()
+            / (gx.rows() * gx.cols() * 3) as f64;
+
+        Ok(1.0 / energy * 100000.0)
+    }
+}
+
+/// Inverse of the share of spectral energy above a cutoff frequency in the grayscale image.
+pub struct FftHighFrequency;
+impl FftHighFrequency {
+    /// Normalized radius (in cycles per pixel) above which a frequency counts as high.
+    const CUTOFF: f64 = 0.1;
+}
+impl YakudoScorer for FftHighFrequency {
+    fn name(&self) -> &'static str {
+        "fft"
+    }
+
+    fn score(&self, image: &Mat) -> anyhow::Result<f64> {
+        let mut gray = Mat::default();
+        opencv::imgproc::cvt_color(image, &mut gray, opencv::imgproc::COLOR_BGR2GRAY, 0)?;
+        let mut float = Mat::default();
+        gray.convert_to(&mut float, opencv::core::CV_64F, 1.0, 0.0)?;
+        let mut spectrum = Mat::default();
+        opencv::core::dft(&float, &mut spectrum, opencv::core::DFT_COMPLEX_OUTPUT, 0)
+            .context("failed to calculate spectrum")?;
+
+        let rows = spectrum.rows();
+        let cols = spectrum.cols();
+        let mut total = 0.0;
+        let mut high = 0.0;
+        for (pos, v) in spectrum.iter::<Vec2d>()? {
+            if pos.x == 0 && pos.y == 0 {
+                // skip the DC component, which only reflects brightness
+                continue;
+            }
+            let fx = pos.x.min(cols - pos.x) as f64 / cols as f64;
+            let fy = pos.y.min(rows - pos.y) as f64 / rows as f64;
+            let energy = v[0].powi(2) + v[1].powi(2);
+            total += energy;
+            if (fx.powi(2) + fy.powi(2)).sqrt() > Self::CUTOFF {
+                high += energy;
+            }
+        }
+
+        Ok(total / high)
+    }
+}
+
+/// Direction and strength of the blur, estimated from how the spectrum falls off along each
+/// direction. Blur along a direction removes the high frequencies along it and leaves the others,
+/// so each direction is judged by its high frequencies relative to its own low ones. The
+/// orientation of edges in the scene changes how much energy a direction has rather than how it is
+/// spread over frequencies, so it largely cancels out.
+#[derive(Clone, Copy, Debug, Serialize)]
+pub struct MotionBlur {
+    /// Direction of the blur in degrees in `[0, 180)`. 0 is horizontal, 90 is vertical.
+    pub angle: f64,
+    /// 0 when the blur is the same in every direction, approaching 1 when it is along a single
+    /// direction.
+    pub anisotropy: f64,
+}
+impl MotionBlur {
+    /// The directions compared, in degrees, as two perpendicular pairs.
+    const DIRECTIONS: [f64; 4] = [0.0, 45.0, 90.0, 135.0];
+    /// The low band in cycles per image along the shorter edge. Blurs up to about an eighth of
+    /// the image long leave it mostly intact, and it is wide enough not to depend on a few
+    /// frequencies.
+    const LOW_CYCLES: (f64, f64) = (1.0, 8.0);
+    /// The high band in cycles per pixel.
+    const HIGH: (f64, f64) = (0.1, 0.5);
+    /// Directions with less low-band energy than this share of the strongest one carry no
+    /// information, e.g. across plain stripes, and are left out.
+    const MIN_SHARE: f64 = 1e-3;
+
+    pub fn estimate(image: &Mat) -> anyhow::Result<Self> {
+        let mut gray = Mat::default();
+        opencv::imgproc::cvt_color(image, &mut gray, opencv::imgproc::COLOR_BGR2GRAY, 0)?;
+        let mut float = Mat::default();
+        gray.convert_to(&mut float, opencv::core::CV_64F, 1.0, 0.0)?;
+        let mut spectrum = Mat::default();
+        opencv::core::dft(&float, &mut spectrum, opencv::core::DFT_COMPLEX_OUTPUT, 0)
+            .context("failed to calculate spectrum")?;
+
+        let rows = spectrum.rows();
+        let cols = spectrum.cols();
+        let short_edge = rows.min(cols) as f64;
+        let low_band = Self::LOW_CYCLES.0 / short_edge..=Self::LOW_CYCLES.1 / short_edge;
+        let high_band = Self::HIGH.0..=Self::HIGH.1;
+        let axes = Self::DIRECTIONS.map(|d| (d.to_radians().cos(), d.to_radians().sin()));
+        let (mut low, mut high) = ([0.0; 4], [0.0; 4]);
+        // signed frequencies in cycles per pixel, with y pointing down as in the image
+        let signed = |i: i32, n: i32| if i <= n / 2 { i } else { i - n } as f64 / n as f64;
+        for (pos, v) in spectrum.iter::<Vec2d>()? {
+            let (fx, fy) = (signed(pos.x, cols), signed(pos.y, rows));
+            let energy = v[0].powi(2) + v[1].powi(2);
+            for (i, (cos, sin)) in axes.iter().enumerate() {
+                let f = (fx * cos + fy * sin).abs();
+                if low_band.contains(&f) {
+                    low[i] += energy;
+                } else if high_band.contains(&f) {
+                    high[i] += energy;
+                }
+            }
+        }
+
+        // the high frequencies left along each direction, lower where the image is blurred
+        let strongest = low.iter().cloned().fold(0.0, f64::max);
+        let sharpness = (0..4)
+            .map(|i| (low[i] > strongest * Self::MIN_SHARE).then_some(high[i] / low[i]))
+            .collect::<Vec<_>>();
+        // in [-1, 1], positive when the first direction is the blurrier one
+        let contrast = |a: usize, b: usize| match (sharpness[a], sharpness[b]) {
+            (Some(a), Some(b)) if a + b > 0.0 => (b - a) / (a + b),
+            _ => 0.0,
+        };
+        let (c, s) = (contrast(0, 2), contrast(1, 3));
+
+        Ok(MotionBlur {
+            angle: (0.5 * s.atan2(c)).to_degrees().rem_euclid(180.0),
+            anisotropy: c.hypot(s).min(1.0),
+        })
+    }
+
+    /// Factor applied to the base score. Scenery seen from a train window streaks horizontally,
+    /// so strong horizontal blur up to doubles the score while isotropic blur leaves it as is.
+    pub fn multiplier(&self) -> f64 {
+        1.0 + self.anisotropy * self.angle.to_radians().cos().abs()
+    }
+
+    pub fn direction(&self) -> &'static str {
+        if self.angle < 22.5 || self.angle >= 157.5 {
+            "横"
+        } else if (67.5..112.5).contains(&self.angle) {
+            "縦"
+        } else {
+            "斜め"
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use opencv::core::{Point, Scalar, Size, CV_8UC3};
+
+    use super::*;
+
+    const SIZE: i32 = 128;
+
+    /// Gray white noise, sharp in every direction, from a fixed seed (SplitMix64).
+    fn noise() -> Mat {
+        let mut state = 1u64;
+        let mut image =
+            Mat::new_rows_cols_with_default(SIZE, SIZE, CV_8UC3, Scalar::all(0.0)).unwrap();
+        for pixel in image.data_bytes_mut().unwrap().chunks_exact_mut(3) {
+            state = state.wrapping_add(0x9e3779b97f4a7c15);
+            let mut z = state;
+            z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+            pixel.fill(((z ^ (z >> 31)) >> 56) as u8);
+        }
+        image
+    }
+
+    fn box_blur(image: &Mat, width: i32, height: i32) -> Mat {
+        let mut blurred = Mat::default();
+        opencv::imgproc::blur(
+            image,
+            &mut blurred,
+            Size::new(width, height),
+            Point::new(-1, -1),
+            opencv::core::BORDER_DEFAULT,
+        )
+        .unwrap();
+        blurred
+    }
+
+    /// The distance between two angles in `[0, 180)`.
+    fn angle_between(a: f64, b: f64) -> f64 {
+        let d = (a - b).rem_euclid(180.0);
+        d.min(180.0 - d)
+    }
+
+    #[test]
+    fn sharp_image() {
+        let blur = MotionBlur::estimate(&noise()).unwrap();
+        assert!(blur.anisotropy < 0.15, "{:?}", blur);
+    }
+
+    #[test]
+    fn horizontal_blur() {
+        let blur = MotionBlur::estimate(&box_blur(&noise(), 15, 1)).unwrap();
+        assert!(angle_between(blur.angle, 0.0) < 10.0, "{:?}", blur);
+        assert!(blur.anisotropy > 0.8, "{:?}", blur);
+        assert!(blur.multiplier() > 1.7, "{:?}", blur);
+        assert_eq!(blur.direction(), "横");
+    }
+
+    #[test]
+    fn vertical_blur() {
+        let blur = MotionBlur::estimate(&box_blur(&noise(), 1, 15)).unwrap();
+        assert!(angle_between(blur.angle, 90.0) < 10.0, "{:?}", blur);
+        assert!(blur.anisotropy > 0.8, "{:?}", blur);
+        assert!(blur.multiplier() < 1.2, "{:?}", blur);
+        assert_eq!(blur.direction(), "縦");
+    }
+
+    #[test]
+    fn isotropic_blur() {
+        let mut blurred = Mat::default();
+        opencv::imgproc::gaussian_blur(
+            &noise(),
+            &mut blurred,
+            Size::new(0, 0),
+            2.0,
+            2.0,
+            opencv::core::BORDER_DEFAULT,
+        )
+        .unwrap();
+        let blur = MotionBlur::estimate(&blurred).unwrap();
+        assert!(blur.anisotropy < 0.25, "{:?}", blur);
+    }
+
+    #[test]
+    fn stripes_are_not_blur() {
+        // sharp vertical stripes have no gradient along them, which is not the same as a blur
+        let row = noise().row(0).unwrap();
+        let mut stripes = Mat::default();
+        opencv::core::repeat(&row, SIZE, 1, &mut stripes).unwrap();
+        let blur = MotionBlur::estimate(&stripes).unwrap();
+        assert!(blur.anisotropy < 0.05, "{:?}", blur);
+    }
+}
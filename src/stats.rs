@@ -0,0 +1,186 @@
+use anyhow::Context;
+use chrono::{Duration, Local, NaiveDate};
+use sea_orm::{prelude::*, QueryOrder};
+use serde::Serialize;
+
+use crate::{database::get_db, entity::yakudo_score, users::UserKey};
+
+/// The number of latest verdicts kept in `UserStats::rank_history`.
+const RANK_HISTORY_LEN: usize = 10;
+
+#[derive(Debug, Default, Serialize)]
+pub struct UserStats {
+    pub posts: usize,
+    /// The best and average of the valid (positive) scores.
+    pub best: Option<f64>,
+    pub best_note_id: Option<String>,
+    pub average: Option<f64>,
+    /// The number of consecutive days with a post up to today, or up to yesterday if the user has
+    /// not posted yet today.
+    pub streak: usize,
+    pub longest_streak: usize,
+    /// The verdict ranks of the latest posts, oldest first.
+    pub rank_history: Vec<String>,
+}
+
+/// Collects the statistics of the yakudos of `user`, limited to `hashtag` if given.
+pub async fn user_stats(user: &UserKey, hashtag: Option<&str>) -> anyhow::Result<UserStats> {
+    let mut query = yakudo_score::Entity::find().filter(user.condition());
+    if let Some(hashtag) = hashtag {
+        query = query.filter(yakudo_score::Column::Hashtag.eq(hashtag));
+    }
+    let yakudos = query
+        .order_by_asc(yakudo_score::Column::Date)
+        .all(get_db().await?)
+        .await
+        .context("failed to get yakudos of user")?;
+    Ok(stats_of(&yakudos, Local::now().date_naive()))
+}
+
+/// The part of `user_stats` after the query. `yakudos` must be sorted by date.
+fn stats_of(yakudos: &[yakudo_score::Model], today: NaiveDate) -> UserStats {
+    let valid = yakudos
+        .iter()
+        .map(|y| y.score)
+        .filter(|score| *score > 0.0)
+        .collect::<Vec<_>>();
+    let best = yakudos
+        .iter()
+        .filter(|y| y.score > 0.0)
+        .max_by(|a, b| a.score.total_cmp(&b.score));
+
+    let mut days = yakudos
+        .iter()
+        .map(|y| y.date.with_timezone(&Local).date_naive())
+        .collect::<Vec<_>>();
+    days.dedup();
+    let (streak, longest_streak) = streaks(&days, today);
+
+    let rank_history = yakudos
+        .iter()
+        .filter_map(|y| y.verdict.clone())
+        .collect::<Vec<_>>();
+
+    UserStats {
+        posts: yakudos.len(),
+        best: best.map(|y| y.score),
+        best_note_id: best.map(|y| y.note_id.clone()),
+        average: if valid.is_empty() {
+            None
+        } else {
+            Some(valid.iter().sum::<f64>() / valid.len() as f64)
+        },
+        streak,
+        longest_streak,
+        rank_history: rank_history[rank_history.len().saturating_sub(RANK_HISTORY_LEN)..].to_vec(),
+    }
+}
+
+/// Returns the current and the longest run of consecutive days in `days`, which must be sorted
+/// and deduplicated.
+fn streaks(days: &[NaiveDate], today: NaiveDate) -> (usize, usize) {
+    let mut longest = 0;
+    let mut run = 0;
+    for (i, day) in days.iter().enumerate() {
+        if i > 0 && *day - days[i - 1] == Duration::days(1) {
+            run += 1;
+        } else {
+            run = 1;
+        }
+        longest = longest.max(run);
+    }
+
+    let current = match days.last() {
+        Some(last) if today - *last <= Duration::days(1) => run,
+        _ => 0,
+    };
+    (current, longest)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    fn day(d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2026, 10, d).unwrap()
+    }
+
+    fn yakudo(id: i32, d: u32, hour: u32, score: f64, verdict: &str) -> yakudo_score::Model {
+        yakudo_score::Model {
+            id,
+            username: "alice".to_string(),
+            user_id: Some("a".to_string()),
+            user_host: None,
+            note_id: format!("note{}", id),
+            quote_id: format!("quote{}", id),
+            score,
+            date: Local.with_ymd_and_hms(2026, 10, d, hour, 0, 0).unwrap(),
+            verdict: Some(verdict.to_string()),
+            normalize_long_edge: None,
+            normalize_denoise_sigma: None,
+            hashtag: Some("mis1yakudo".to_string()),
+            aggregate: None,
+            scorer_version: None,
+            created_at: None,
+        }
+    }
+
+    #[test]
+    fn no_days() {
+        assert_eq!(streaks(&[], day(10)), (0, 0));
+    }
+
+    #[test]
+    fn gaps_end_runs() {
+        let days = [day(1), day(2), day(3), day(5), day(6)];
+        assert_eq!(streaks(&days, day(6)), (2, 3));
+    }
+
+    #[test]
+    fn streak_up_to_yesterday_is_current() {
+        let days = [day(4), day(5)];
+        assert_eq!(streaks(&days, day(5)), (2, 2));
+        assert_eq!(streaks(&days, day(6)), (2, 2));
+        assert_eq!(streaks(&days, day(7)), (0, 2));
+    }
+
+    #[test]
+    fn posts_on_the_same_day_count_once() {
+        let yakudos = [
+            yakudo(1, 4, 9, 2.0, "A"),
+            yakudo(2, 5, 9, 0.0, "B"),
+            yakudo(3, 5, 21, 4.0, "C"),
+            yakudo(4, 6, 9, 3.0, "D"),
+        ];
+        let stats = stats_of(&yakudos, day(6));
+        assert_eq!(stats.posts, 4);
+        assert_eq!((stats.streak, stats.longest_streak), (3, 3));
+        // the zero score is not valid
+        assert_eq!(stats.best, Some(4.0));
+        assert_eq!(stats.best_note_id.as_deref(), Some("note3"));
+        assert_eq!(stats.average, Some(3.0));
+        assert_eq!(stats.rank_history, ["A", "B", "C", "D"]);
+    }
+
+    #[test]
+    fn latest_ranks_are_kept() {
+        let yakudos = (1..=12)
+            .map(|id| yakudo(id as i32, id, 12, 1.0, &id.to_string()))
+            .collect::<Vec<_>>();
+        let stats = stats_of(&yakudos, day(20));
+        assert_eq!(stats.rank_history.len(), RANK_HISTORY_LEN);
+        assert_eq!(stats.rank_history.first().map(String::as_str), Some("3"));
+        assert_eq!(stats.rank_history.last().map(String::as_str), Some("12"));
+        assert_eq!((stats.streak, stats.longest_streak), (0, 12));
+    }
+
+    #[test]
+    fn no_valid_scores() {
+        let stats = stats_of(&[yakudo(1, 1, 12, 0.0, "F")], day(1));
+        assert_eq!(stats.best, None);
+        assert_eq!(stats.average, None);
+        assert_eq!(stats.streak, 1);
+    }
+}
@@ -0,0 +1,212 @@
+use std::{collections::HashMap, sync::Arc};
+
+use anyhow::Context;
+use chrono::{Duration, Utc};
+use misskey::{
+    model::{id::Id, note::Note, user::User},
+    ClientExt,
+};
+use sea_orm::{prelude::*, sea_query::OnConflict, ActiveValue, Condition, QueryOrder};
+use tokio::time::sleep;
+
+use crate::{
+    database::get_db,
+    entity::{user, yakudo_score},
+    metrics,
+    misskey::Misskey,
+};
+
+/// How old, in days, a cached profile gets before `refresh_users` fetches it again.
+const USER_MAX_AGE_DAYS: i64 = 1;
+
+/// `username` for local users and `username@host` for remote ones.
+pub fn acct(username: &str, host: Option<&str>) -> String {
+    match host {
+        Some(host) => format!("{}@{}", username, host),
+        None => username.to_string(),
+    }
+}
+
+/// A user as the records know them. Users are identified by their ID, which is unique across
+/// hosts, unlike the username.
+#[derive(Clone, Debug)]
+pub struct UserKey {
+    pub id: String,
+    pub username: String,
+    pub host: Option<String>,
+}
+impl UserKey {
+    pub fn acct(&self) -> String {
+        acct(&self.username, self.host.as_deref())
+    }
+
+    /// Matches the records of this user. Records made before user IDs were stored are matched
+    /// by username for local users only, as their host is unknown.
+    pub fn condition(&self) -> Condition {
+        let condition = Condition::any().add(yakudo_score::Column::UserId.eq(self.id.as_str()));
+        if self.host.is_none() {
+            condition.add(
+                Condition::all()
+                    .add(yakudo_score::Column::UserId.is_null())
+                    .add(yakudo_score::Column::Username.eq(self.username.as_str())),
+            )
+        } else {
+            condition
+        }
+    }
+
+    /// Like `condition`, for records already fetched.
+    pub fn matches(&self, yakudo: &yakudo_score::Model) -> bool {
+        match &yakudo.user_id {
+            Some(user_id) => *user_id == self.id,
+            None => self.host.is_none() && yakudo.username == self.username,
+        }
+    }
+}
+impl From<&User> for UserKey {
+    fn from(user: &User) -> Self {
+        Self {
+            id: user.id.to_string(),
+            username: user.username.clone(),
+            host: user.host.clone(),
+        }
+    }
+}
+impl From<&user::Model> for UserKey {
+    fn from(user: &user::Model) -> Self {
+        Self {
+            id: user.id.clone(),
+            username: user.username.clone(),
+            host: user.host.clone(),
+        }
+    }
+}
+
+/// Gives the records made before user IDs were stored the ID of the local user with their
+/// username, as `UserKey::condition` matches them, so that they are not counted apart from the
+/// user's newer records.
+pub async fn resolve_legacy(yakudos: &mut [yakudo_score::Model]) -> anyhow::Result<()> {
+    let usernames = yakudos
+        .iter()
+        .filter(|y| y.user_id.is_none())
+        .map(|y| y.username.clone())
+        .collect::<Vec<_>>();
+    if usernames.is_empty() {
+        return Ok(());
+    }
+    let ids = user::Entity::find()
+        .filter(user::Column::Host.is_null())
+        .filter(user::Column::Username.is_in(usernames))
+        .all(get_db().await?)
+        .await
+        .context("failed to get users")?
+        .into_iter()
+        .map(|user| (user.username, user.id))
+        .collect();
+    assign_local_ids(yakudos, &ids);
+    Ok(())
+}
+
+/// The part of `resolve_legacy` after the lookup. `ids` maps local usernames to user IDs.
+pub fn assign_local_ids(yakudos: &mut [yakudo_score::Model], ids: &HashMap<String, String>) {
+    for yakudo in yakudos.iter_mut().filter(|y| y.user_id.is_none()) {
+        yakudo.user_id = ids.get(&yakudo.username).cloned();
+    }
+}
+
+/// Caches the profile of `user` as served by the API, replacing the one cached before.
+pub async fn remember(user: &User) -> anyhow::Result<()> {
+    let db = get_db().await?;
+    let model = user::ActiveModel {
+        id: ActiveValue::Set(user.id.to_string()),
+        username: ActiveValue::Set(user.username.clone()),
+        host: ActiveValue::Set(user.host.clone()),
+        name: ActiveValue::Set(user.name.clone()),
+        avatar_url: ActiveValue::Set(user.avatar_url.as_ref().map(|url| url.to_string())),
+        updated_at: ActiveValue::Set(Utc::now()),
+    };
+    // an upsert, as the same user can be remembered by several streams at once
+    user::Entity::insert(model)
+        .on_conflict(
+            OnConflict::column(user::Column::Id)
+                .update_columns([
+                    user::Column::Username,
+                    user::Column::Host,
+                    user::Column::Name,
+                    user::Column::AvatarUrl,
+                    user::Column::UpdatedAt,
+                ])
+                .to_owned(),
+        )
+        .exec(db)
+        .await
+        .context("failed to save user")?;
+    Ok(())
+}
+
+/// Refreshes the cached profiles not updated for `USER_MAX_AGE_DAYS`, for the users who have not
+/// posted or sent a command since, whose profiles are still shown on the web pages.
+pub async fn refresh_users(misskey: Arc<Misskey>) -> anyhow::Result<()> {
+    info!("refreshing user profiles");
+
+    let users = user::Entity::find()
+        .filter(user::Column::UpdatedAt.lt(Utc::now() - Duration::days(USER_MAX_AGE_DAYS)))
+        .order_by_asc(user::Column::UpdatedAt)
+        .all(get_db().await?)
+        .await
+        .context("failed to get users")?;
+
+    for cached in users {
+        let user_id = cached.id.parse::<Id<User>>()?;
+        match metrics::api_result("get_user", misskey.get_user(user_id).await) {
+            Ok(user) => remember(&user).await?,
+            // deleted or suspended. the cached profile is kept for their records
+            Err(err) => warn!("failed to get user {}: {}", cached.id, err),
+        }
+        sleep(std::time::Duration::from_secs(1)).await;
+    }
+
+    Ok(())
+}
+
+pub async fn find(id: &str) -> anyhow::Result<Option<user::Model>> {
+    user::Entity::find_by_id(id.to_string())
+        .one(get_db().await?)
+        .await
+        .context("failed to get user")
+}
+
+/// Fills in the users of the records made before user IDs were stored, from their notes. The
+/// records whose note is gone are left as they are, and tried again on the next start.
+pub async fn backfill_yakudo_users(misskey: Arc<Misskey>) -> anyhow::Result<()> {
+    let db = get_db().await?;
+    let yakudos = yakudo_score::Entity::find()
+        .filter(yakudo_score::Column::UserId.is_null())
+        .all(db)
+        .await
+        .context("failed to get yakudos without user")?;
+    if yakudos.is_empty() {
+        return Ok(());
+    }
+    info!("backfilling the users of {} yakudos", yakudos.len());
+
+    let mut filled = 0;
+    for yakudo in yakudos {
+        let note_id = yakudo.note_id.parse::<Id<Note>>()?;
+        match metrics::api_result("get_note", misskey.get_note(note_id).await) {
+            Ok(note) => {
+                remember(&note.user).await?;
+                let mut yakudo: yakudo_score::ActiveModel = yakudo.into();
+                yakudo.user_id = ActiveValue::Set(Some(note.user.id.to_string()));
+                yakudo.user_host = ActiveValue::Set(note.user.host);
+                yakudo.update(db).await.context("failed to update yakudo")?;
+                filled += 1;
+            }
+            Err(err) => warn!("failed to get note {}: {}", yakudo.note_id, err),
+        }
+        sleep(std::time::Duration::from_secs(1)).await;
+    }
+
+    info!("backfilled the users of {} yakudos", filled);
+    Ok(())
+}
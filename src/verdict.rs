@@ -0,0 +1,133 @@
+use serde::Deserialize;
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Verdict {
+    pub rank: String,
+    /// The minimum score for this verdict.
+    pub threshold: f64,
+    pub message: String,
+}
+
+/// Verdict tiers ordered from the highest threshold to the lowest.
+#[derive(Debug, Deserialize)]
+#[serde(try_from = "Vec<Verdict>")]
+pub struct VerdictTable {
+    verdicts: Vec<Verdict>,
+}
+impl VerdictTable {
+    pub fn new(mut verdicts: Vec<Verdict>) -> anyhow::Result<Self> {
+        if verdicts.is_empty() {
+            return Err(anyhow::anyhow!("at least one verdict is required"));
+        }
+        if let Some(verdict) = verdicts.iter().find(|v| v.threshold.is_nan()) {
+            return Err(anyhow::anyhow!(
+                "threshold of verdict {} is not a number",
+                verdict.rank
+            ));
+        }
+        verdicts.sort_by(|a, b| b.threshold.total_cmp(&a.threshold));
+        Ok(Self { verdicts })
+    }
+
+    /// Returns the verdict with the highest threshold not above `score`, or the lowest verdict if
+    /// `score` is below every threshold.
+    pub fn judge(&self, score: f64) -> &Verdict {
+        self.verdicts
+            .iter()
+            .find(|v| score >= v.threshold)
+            .unwrap_or_else(|| self.verdicts.last().unwrap())
+    }
+}
+impl TryFrom<Vec<Verdict>> for VerdictTable {
+    type Error = anyhow::Error;
+
+    fn try_from(verdicts: Vec<Verdict>) -> Result<Self, Self::Error> {
+        Self::new(verdicts)
+    }
+}
+impl Default for VerdictTable {
+    fn default() -> Self {
+        Self {
+            verdicts: vec![
+                Verdict {
+                    rank: "A".to_string(),
+                    threshold: 150.0,
+                    message: "GoodYakudo!".to_string(),
+                },
+                Verdict {
+                    rank: "C".to_string(),
+                    threshold: f64::NEG_INFINITY,
+                    message: "もっとyakudoしろ！".to_string(),
+                },
+            ],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn verdict(rank: &str, threshold: f64) -> Verdict {
+        Verdict {
+            rank: rank.to_string(),
+            threshold,
+            message: String::new(),
+        }
+    }
+
+    fn ranks(table: &VerdictTable) -> Vec<&str> {
+        table.verdicts.iter().map(|v| v.rank.as_str()).collect()
+    }
+
+    #[test]
+    fn sorted_by_threshold() {
+        let table = VerdictTable::new(vec![
+            verdict("B", 50.0),
+            verdict("S", 300.0),
+            verdict("A", 100.0),
+        ])
+        .unwrap();
+        assert_eq!(ranks(&table), ["S", "A", "B"]);
+    }
+
+    #[test]
+    fn invalid_tables() {
+        assert!(VerdictTable::new(vec![]).is_err());
+        assert!(VerdictTable::new(vec![verdict("A", 100.0), verdict("B", f64::NAN)]).is_err());
+    }
+
+    #[test]
+    fn judge() {
+        let table = VerdictTable::new(vec![
+            verdict("B", 50.0),
+            verdict("S", 300.0),
+            verdict("A", 100.0),
+        ])
+        .unwrap();
+        assert_eq!(table.judge(1000.0).rank, "S");
+        // thresholds are inclusive
+        assert_eq!(table.judge(100.0).rank, "A");
+        assert_eq!(table.judge(99.9).rank, "B");
+        // below every threshold, and scores that are not numbers, get the lowest verdict
+        assert_eq!(table.judge(10.0).rank, "B");
+        assert_eq!(table.judge(f64::NAN).rank, "B");
+    }
+
+    #[test]
+    fn unknown_fields_are_rejected() {
+        #[derive(Debug, Deserialize)]
+        struct Config {
+            #[allow(dead_code)]
+            verdicts: VerdictTable,
+        }
+        let parse = |toml: &str| toml::from_str::<Config>(toml);
+        assert!(parse("[[verdicts]]\nrank = \"A\"\nthreshold = 1.0\nmessage = \"\"\n").is_ok());
+        // an unknown key, e.g. a typo, is an error rather than silently ignored
+        assert!(parse(
+            "[[verdicts]]\nrank = \"A\"\nthreshold = 1.0\nmessage = \"\"\nemoji = \"\"\n"
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,132 @@
+use std::{
+    fs::OpenOptions,
+    io::{ErrorKind, Write},
+    path::PathBuf,
+};
+
+use anyhow::Context;
+use opencv::{core::Mat, prelude::*, videoio};
+use rand::Rng;
+use serde::Deserialize;
+
+use crate::scorer::{ImageScore, Pipeline};
+
+/// How frames are sampled from attached videos.
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct VideoSampling {
+    /// Video scoring is opt-in. Videos are rejected when this is `false`.
+    pub enabled: bool,
+    /// The number of frames, spread evenly over the video, to score.
+    pub frames: usize,
+}
+impl Default for VideoSampling {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            frames: 10,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct VideoScore {
+    pub frames: Vec<ImageScore>,
+}
+impl VideoScore {
+    pub fn scores(&self) -> Vec<f64> {
+        self.frames.iter().map(|f| f.score).collect()
+    }
+
+    pub fn best(&self) -> f64 {
+        self.frames
+            .iter()
+            .map(|f| f.score)
+            .fold(f64::NEG_INFINITY, f64::max)
+    }
+
+    pub fn mean(&self) -> f64 {
+        self.frames.iter().map(|f| f.score).sum::<f64>() / self.frames.len() as f64
+    }
+}
+
+/// Scores frames sampled from an encoded video. OpenCV can only open videos from a file or URL,
+/// so the bytes are written to a temporary file first.
+pub fn score_video_bytes(pipeline: &Pipeline, bytes: &[u8]) -> anyhow::Result<VideoScore> {
+    let sampling = pipeline.video;
+    let file = TempFile::create(bytes).context("failed to write video to temporary file")?;
+
+    let mut capture = videoio::VideoCapture::from_file(
+        file.0.to_str().context("invalid temporary file path")?,
+        videoio::CAP_ANY,
+    )?;
+    if !capture.is_opened()? {
+        return Err(anyhow::anyhow!("failed to open video"));
+    }
+
+    let frame_count = capture.get(videoio::CAP_PROP_FRAME_COUNT)? as usize;
+    let mut frames = vec![];
+    let mut frame = Mat::default();
+
+    if frame_count > 0 {
+        for i in 0..sampling.frames.min(frame_count) {
+            // the middle frame of each of `sampling.frames` equal segments
+            let index = frame_count * (2 * i + 1) / (2 * sampling.frames.min(frame_count));
+            capture.set(videoio::CAP_PROP_POS_FRAMES, index as f64)?;
+            if !capture.read(&mut frame)? || frame.empty() {
+                continue;
+            }
+            frames.push(pipeline.score_image(&frame)?);
+        }
+    } else {
+        // some containers do not report the frame count, so just score the first frames
+        while frames.len() < sampling.frames && capture.read(&mut frame)? && !frame.empty() {
+            frames.push(pipeline.score_image(&frame)?);
+        }
+    }
+
+    if frames.is_empty() {
+        return Err(anyhow::anyhow!("no frame could be read from video"));
+    }
+
+    Ok(VideoScore { frames })
+}
+
+struct TempFile(PathBuf);
+impl TempFile {
+    /// Creates a new file with a random name in the temporary directory and writes `bytes` to
+    /// it. The file is created exclusively, so an existing file (or a link planted by another
+    /// user of the shared directory) is never written to.
+    fn create(bytes: &[u8]) -> std::io::Result<Self> {
+        let mut options = OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+
+        for _ in 0..16 {
+            let path = std::env::temp_dir().join(format!(
+                "yakudobot-{}-{:016x}.video",
+                std::process::id(),
+                rand::thread_rng().gen::<u64>()
+            ));
+            match options.open(&path) {
+                Ok(mut file) => {
+                    let temp_file = TempFile(path);
+                    file.write_all(bytes)?;
+                    return Ok(temp_file);
+                }
+                Err(err) if err.kind() == ErrorKind::AlreadyExists => continue,
+                Err(err) => return Err(err),
+            }
+        }
+        Err(std::io::Error::new(
+            ErrorKind::AlreadyExists,
+            "failed to find an unused temporary file name",
+        ))
+    }
+}
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
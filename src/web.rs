@@ -0,0 +1,412 @@
+use std::fmt::Write;
+
+use anyhow::Context;
+use chrono::{DateTime, Local};
+use hyper::{header::CONTENT_TYPE, Body, Method, Response, StatusCode, Uri};
+use reqwest::Url;
+use sea_orm::{prelude::*, FromQueryResult, QueryOrder, QuerySelect};
+use serde::Serialize;
+
+use crate::{
+    config,
+    database::get_db,
+    entity::yakudo_score,
+    misskey::note_url,
+    report,
+    stats::{self, UserStats},
+    users::{self, UserKey},
+};
+
+/// The default and maximum number of entries in the all-time ranking.
+const TOP_LIMIT: u64 = 20;
+const MAX_TOP_LIMIT: u64 = 100;
+/// The number of latest posts on a user page.
+const HISTORY_LIMIT: u64 = 50;
+/// The lower bounds of the buckets of the score distribution.
+const DISTRIBUTION_BOUNDS: &[f64] = &[0.0, 10.0, 25.0, 50.0, 100.0, 150.0, 200.0, 300.0, 500.0];
+
+#[derive(Serialize)]
+struct YakudoView {
+    /// `None` for the records made before user IDs were stored.
+    user_id: Option<String>,
+    /// `username` or `username@host`.
+    acct: String,
+    score: f64,
+    verdict: Option<String>,
+    hashtag: Option<String>,
+    date: DateTime<Local>,
+    url: String,
+}
+impl From<yakudo_score::Model> for YakudoView {
+    fn from(yakudo: yakudo_score::Model) -> Self {
+        Self {
+            url: note_url(&yakudo.note_id),
+            acct: yakudo.acct(),
+            user_id: yakudo.user_id,
+            score: yakudo.score,
+            verdict: yakudo.verdict,
+            hashtag: yakudo.hashtag,
+            date: yakudo.date,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct RankedView {
+    place: usize,
+    #[serde(flatten)]
+    yakudo: YakudoView,
+}
+
+#[derive(Serialize)]
+struct UserView {
+    id: String,
+    username: String,
+    host: Option<String>,
+    /// The display name and avatar as of the user's latest yakudo or command.
+    name: Option<String>,
+    avatar_url: Option<String>,
+    #[serde(flatten)]
+    stats: UserStats,
+    history: Vec<YakudoView>,
+}
+
+#[derive(Serialize)]
+struct Bucket {
+    /// Inclusive. `None` for the bucket of the invalid (not positive) scores.
+    min: Option<f64>,
+    /// Exclusive. `None` for the last bucket.
+    max: Option<f64>,
+    count: usize,
+}
+
+#[derive(FromQueryResult)]
+struct ScoreOnly {
+    score: f64,
+}
+
+/// Serves the leaderboard pages and their JSON API. The rankings and the distribution take an
+/// optional `hashtag` query parameter, which defaults to the first configured hashtag so that
+/// scores of hashtags scored differently are never mixed, and `/api/top` a `limit`.
+pub async fn handle(method: &Method, uri: &Uri) -> Response<Body> {
+    if method != Method::GET {
+        return not_found();
+    }
+    let url = match Url::parse(&format!("http://localhost{}", uri)) {
+        Ok(url) => url,
+        Err(_) => return not_found(),
+    };
+    let query = |key: &str| {
+        url.query_pairs()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.into_owned())
+    };
+    let hashtag = query("hashtag").unwrap_or_else(|| config::get().hashtags[0].name.clone());
+    let hashtag = hashtag.as_str();
+
+    let result = match url.path().split('/').collect::<Vec<_>>()[1..] {
+        [""] => index(hashtag).await.map(html),
+        ["users", id] if is_id(id) => user_page(id)
+            .await
+            .map(|page| page.map(html).unwrap_or_else(not_found)),
+        ["api", "today"] => today(hashtag).await.map(|v| json(&v)),
+        ["api", "top"] => {
+            let limit = query("limit")
+                .and_then(|limit| limit.parse().ok())
+                .unwrap_or(TOP_LIMIT)
+                .min(MAX_TOP_LIMIT);
+            top(hashtag, limit).await.map(|v| json(&v))
+        }
+        ["api", "distribution"] => distribution(hashtag).await.map(|v| json(&v)),
+        ["api", "users", id] if is_id(id) => user(id)
+            .await
+            .map(|v| v.map(|v| json(&v)).unwrap_or_else(not_found)),
+        _ => return not_found(),
+    };
+
+    result.unwrap_or_else(|err| {
+        error!("failed to serve {}: {:#}", url.path(), err);
+        let mut response = Response::new(Body::empty());
+        *response.status_mut() = StatusCode::INTERNAL_SERVER_ERROR;
+        response
+    })
+}
+
+fn is_id(s: &str) -> bool {
+    !s.is_empty() && s.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Today's ranking of every user, so far.
+async fn today(hashtag: &str) -> anyhow::Result<Vec<RankedView>> {
+    let today = Local::now().date_naive();
+
+    let yakudos = report::yakudos_between(hashtag, today, today.succ_opt().unwrap())
+        .await?
+        .into_iter()
+        .filter(|y| y.score > 0.0)
+        .collect();
+    Ok(report::rank(yakudos, usize::MAX)
+        .into_iter()
+        .map(|ranked| RankedView {
+            place: ranked.place,
+            yakudo: ranked.yakudo.into(),
+        })
+        .collect())
+}
+
+/// The best posts of all time. A user can appear more than once.
+async fn top(hashtag: &str, limit: u64) -> anyhow::Result<Vec<YakudoView>> {
+    let mut yakudos = yakudo_score::Entity::find()
+        .filter(yakudo_score::Column::Score.gt(0.0))
+        .filter(yakudo_score::Column::Hashtag.eq(hashtag))
+        .order_by_desc(yakudo_score::Column::Score)
+        .limit(limit)
+        .all(get_db().await?)
+        .await
+        .context("failed to get yakudos")?;
+    users::resolve_legacy(&mut yakudos).await?;
+    Ok(yakudos.into_iter().map(YakudoView::from).collect())
+}
+
+async fn distribution(hashtag: &str) -> anyhow::Result<Vec<Bucket>> {
+    let scores = yakudo_score::Entity::find()
+        .select_only()
+        .column(yakudo_score::Column::Score)
+        .filter(yakudo_score::Column::Hashtag.eq(hashtag))
+        .into_model::<ScoreOnly>()
+        .all(get_db().await?)
+        .await
+        .context("failed to get scores")?;
+
+    let mut buckets = vec![Bucket {
+        min: None,
+        max: Some(0.0),
+        count: 0,
+    }];
+    for (i, min) in DISTRIBUTION_BOUNDS.iter().enumerate() {
+        buckets.push(Bucket {
+            min: Some(*min),
+            max: DISTRIBUTION_BOUNDS.get(i + 1).copied(),
+            count: 0,
+        });
+    }
+    for ScoreOnly { score } in scores {
+        // the first bucket takes -inf and 0, like the rankings
+        let i = if score > 0.0 {
+            DISTRIBUTION_BOUNDS
+                .iter()
+                .rposition(|min| score >= *min)
+                .unwrap()
+                + 1
+        } else {
+            0
+        };
+        buckets[i].count += 1;
+    }
+    Ok(buckets)
+}
+
+/// The profile, statistics and latest posts of a user known to the `users` table.
+async fn user(id: &str) -> anyhow::Result<Option<UserView>> {
+    let user = match users::find(id).await? {
+        Some(user) => user,
+        None => return Ok(None),
+    };
+    let key = UserKey::from(&user);
+    let history = yakudo_score::Entity::find()
+        .filter(key.condition())
+        .order_by_desc(yakudo_score::Column::Date)
+        .limit(HISTORY_LIMIT)
+        .all(get_db().await?)
+        .await
+        .context("failed to get yakudos of user")?;
+    Ok(Some(UserView {
+        stats: stats::user_stats(&key, None).await?,
+        history: history.into_iter().map(YakudoView::from).collect(),
+        id: user.id,
+        username: user.username,
+        host: user.host,
+        name: user.name,
+        avatar_url: user.avatar_url,
+    }))
+}
+
+async fn index(hashtag: &str) -> anyhow::Result<String> {
+    let config = config::get();
+
+    let mut body = String::new();
+    if config.hashtags.len() > 1 {
+        body.push_str("<p>");
+        for h in &config.hashtags {
+            write!(body, "<a href=\"/?hashtag={0}\">#{0}</a> ", escape(&h.name))?;
+        }
+        body.push_str("</p>");
+    }
+
+    write!(body, "<h2>今日のランキング #{}</h2>", escape(hashtag))?;
+    let today = today(hashtag).await?;
+    if today.is_empty() {
+        body.push_str("<p>今日のyakudoはまだありません</p>");
+    } else {
+        body.push_str("<table><tr><th>順位</th><th>ユーザー</th><th>スコア</th><th>判定</th></tr>");
+        for ranked in &today {
+            write!(
+                body,
+                "<tr><td>{}位</td>{}</tr>",
+                ranked.place,
+                row(&ranked.yakudo)
+            )?;
+        }
+        body.push_str("</table>");
+    }
+
+    body.push_str("<h2>歴代トップ</h2>");
+    body.push_str("<table><tr><th>日時</th><th>ユーザー</th><th>スコア</th><th>判定</th></tr>");
+    for yakudo in top(hashtag, TOP_LIMIT).await? {
+        write!(
+            body,
+            "<tr><td>{}</td>{}</tr>",
+            yakudo.date.format("%Y-%m-%d"),
+            row(&yakudo)
+        )?;
+    }
+    body.push_str("</table>");
+
+    body.push_str("<h2>スコアの分布</h2><table>");
+    let buckets = distribution(hashtag).await?;
+    let most = buckets.iter().map(|b| b.count).max().unwrap_or(0).max(1);
+    for bucket in &buckets {
+        let label = match (bucket.min, bucket.max) {
+            (None, _) => "-inf".to_string(),
+            (Some(min), Some(max)) => format!("{}〜{}", min, max),
+            (Some(min), None) => format!("{}〜", min),
+        };
+        write!(
+            body,
+            "<tr><td>{}</td><td>{}</td><td>{}</td></tr>",
+            label,
+            "█".repeat(bucket.count * 40 / most),
+            bucket.count
+        )?;
+    }
+    body.push_str("</table>");
+
+    Ok(page("yakudo leaderboard", &body))
+}
+
+async fn user_page(id: &str) -> anyhow::Result<Option<String>> {
+    let user = match user(id).await? {
+        Some(user) => user,
+        None => return Ok(None),
+    };
+    let stats = &user.stats;
+    let acct = users::acct(&user.username, user.host.as_deref());
+
+    let mut body = String::from("<h2>");
+    if let Some(avatar_url) = &user.avatar_url {
+        write!(
+            body,
+            "<img src=\"{}\" alt=\"\" width=\"48\" height=\"48\"> ",
+            escape(avatar_url)
+        )?;
+    }
+    match &user.name {
+        Some(name) => write!(body, "{} (@{})", escape(name), escape(&acct))?,
+        None => write!(body, "@{}", escape(&acct))?,
+    }
+    body.push_str("</h2><ul>");
+    write!(body, "<li>投稿:{}件</li>", stats.posts)?;
+    if let Some(best) = stats.best {
+        write!(body, "<li>自己ベスト:{:.3}</li>", best)?;
+    }
+    if let Some(average) = stats.average {
+        write!(body, "<li>平均スコア:{:.3}</li>", average)?;
+    }
+    write!(
+        body,
+        "<li>連続投稿:{}日 (最長{}日)</li></ul>",
+        stats.streak, stats.longest_streak
+    )?;
+
+    body.push_str("<table><tr><th>日時</th><th>ユーザー</th><th>スコア</th><th>判定</th></tr>");
+    for yakudo in &user.history {
+        write!(
+            body,
+            "<tr><td>{}</td>{}</tr>",
+            yakudo.date.format("%Y-%m-%d %H:%M"),
+            row(yakudo)
+        )?;
+    }
+    body.push_str("</table>");
+
+    Ok(Some(page(&format!("{} - yakudo leaderboard", acct), &body)))
+}
+
+/// The user, score and verdict cells of a table row. Users are linked to their page unless the
+/// record predates user IDs.
+fn row(yakudo: &YakudoView) -> String {
+    let user = match &yakudo.user_id {
+        Some(user_id) => format!(
+            "<a href=\"/users/{}\">{}</a>",
+            escape(user_id),
+            escape(&yakudo.acct)
+        ),
+        None => escape(&yakudo.acct),
+    };
+    format!(
+        "<td>{}</td><td><a href=\"{}\">{:.3}</a></td><td>{}</td>",
+        user,
+        escape(&yakudo.url),
+        yakudo.score,
+        escape(yakudo.verdict.as_deref().unwrap_or("-"))
+    )
+}
+
+fn page(title: &str, body: &str) -> String {
+    format!(
+        "<!DOCTYPE html><html lang=\"ja\"><head><meta charset=\"utf-8\">\
+         <meta name=\"viewport\" content=\"width=device-width\"><title>{0}</title></head>\
+         <body><h1><a href=\"/\">{0}</a></h1>{1}</body></html>",
+        escape(title),
+        body
+    )
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn html(body: String) -> Response<Body> {
+    let mut response = Response::new(Body::from(body));
+    response
+        .headers_mut()
+        .insert(CONTENT_TYPE, "text/html; charset=utf-8".parse().unwrap());
+    response
+}
+
+fn json(value: &impl Serialize) -> Response<Body> {
+    match serde_json::to_string(value) {
+        Ok(body) => {
+            let mut response = Response::new(Body::from(body));
+            response
+                .headers_mut()
+                .insert(CONTENT_TYPE, "application/json".parse().unwrap());
+            response
+        }
+        Err(err) => {
+            error!("failed to serialize response: {}", err);
+            let mut response = Response::new(Body::empty());
+            *response.status_mut() = StatusCode::INTERNAL_SERVER_ERROR;
+            response
+        }
+    }
+}
+
+fn not_found() -> Response<Body> {
+    let mut response = Response::new(Body::empty());
+    *response.status_mut() = StatusCode::NOT_FOUND;
+    response
+}